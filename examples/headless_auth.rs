@@ -0,0 +1,35 @@
+//! This example shows authorizing as an installed app on a machine without a browser, such as a
+//! server reached over SSH, to retrieve info about the user authorized.
+//!
+//! This example requires registering the app as an installed app at [Reddit](https://www.reddit.com/prefs/apps)
+
+extern crate orca;
+
+use orca::{App, Scopes};
+
+fn input(query: &str) -> String {
+	use std::io::Write;
+	let stdin = std::io::stdin();
+	print!("{}", query);
+	std::io::stdout().flush().unwrap();
+	let mut input = String::new();
+	stdin.read_line(&mut input).unwrap();
+	input.trim().to_string()
+}
+
+fn main() {
+	println!("Please enter the requested information");
+	let id = input("App id: ");
+	let redirect = input("Redirect URI: ");
+	let scopes = Scopes::all();
+
+	let mut reddit = App::new("orca_headless_auth_example", "1.0", "/u/IntrepidPig").unwrap();
+	let authorize = reddit.authorize_url(&id, &redirect, &scopes, true).unwrap();
+	println!("Open this url in a browser on any device and allow the app:\n{}", authorize.url);
+	println!("Your browser will then be sent to a page that doesn't load. Copy its address.");
+	let pasted = input("Address: ");
+	reddit.authorize_installed_app_manual(&id, &redirect, &authorize, &pasted).unwrap();
+
+	let user = reddit.get_self().unwrap();
+	println!("Got data: {}", user);
+}
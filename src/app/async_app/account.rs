@@ -0,0 +1,18 @@
+use hyper::{Body, Request};
+use json::Value;
+
+use app::AsyncApp;
+use net::RedditFuture;
+
+impl AsyncApp {
+	/// Get info of the user currently authorized
+	///
+	/// Note: requires connection to be authorized
+	/// # Returns
+	/// A future resolving to the json value of the user data
+	pub fn get_self(&self) -> RedditFuture<Value> {
+		let req = Request::get(self.conn.oauth_url("/api/v1/me/.json")).body(Body::empty()).unwrap();
+
+		self.conn.run_auth_request(req)
+	}
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use failure::Error;
+use futures::future;
+use futures::Future;
+
+use app::AsyncApp;
+use net::auth::{AuthorizeUrl, OAuth};
+use net::RedditFuture;
+use {ResponseGenFn, Scopes};
+
+impl AsyncApp {
+	/// Authorize this app as a script
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `secret` - The app secret registered on Reddit
+	/// * `username` - The username of the user to authorize as
+	/// * `password` - The password of the user to authorize as
+	pub fn authorize_script(&self, id: &str, secret: &str, username: &str, password: &str) -> RedditFuture<()> {
+		let conn = self.conn.clone();
+		Box::new(OAuth::create_script(&self.conn, id, secret, username, password).map(move |auth| conn.set_auth(Some(auth))))
+	}
+
+	/// Authorize this app as an installed app
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `redirect` - The app redirect URI registered on Reddit
+	/// * `response_gen` - An optional function that generates a hyper Response to give to the user
+	/// based on the result of the authorization attempt. The signature is `(Result<String, InstalledAppError) -> Result<Response, Response>`.
+	/// The result passed in is either Ok with the code recieved, or Err with the error that occurred.
+	/// The value returned should usually be an Ok(Response), but you can return Err(Response) to indicate
+	/// that an error occurred within the function.
+	/// * `scopes` - A reference to a Scopes instance representing the capabilites you are requesting
+	/// as an installed app.
+	pub fn authorize_installed_app<I: Into<Option<Arc<ResponseGenFn>>>>(&self, id: &str, redirect: &str, response_gen: I, scopes: &Scopes) -> RedditFuture<()> {
+		let conn = self.conn.clone();
+		Box::new(OAuth::create_installed_app(&self.conn, id, redirect, response_gen, scopes).map(move |auth| conn.set_auth(Some(auth))))
+	}
+
+	/// Creates the url to send a user to for authorizing this app as an installed or web app
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `redirect` - The app redirect URI registered on Reddit
+	/// * `scopes` - A reference to a Scopes instance representing the capabilites you are requesting
+	/// * `permanent` - Whether to request a refresh token, so the app stays authorized after the
+	/// first token expires
+	pub fn authorize_url(&self, id: &str, redirect: &str, scopes: &Scopes, permanent: bool) -> Result<AuthorizeUrl, Error> {
+		AuthorizeUrl::new(&self.conn, id, redirect, scopes, permanent)
+	}
+
+	/// Authorize this app as an installed app without opening a browser or starting a server. See
+	/// `OAuth::create_installed_app_manual`.
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `redirect` - The app redirect URI registered on Reddit
+	/// * `authorize` - The url the user was sent to, made with `authorize_url`
+	/// * `pasted` - The url the user was redirected to, or its query, as pasted by the user
+	pub fn authorize_installed_app_manual(&self, id: &str, redirect: &str, authorize: &AuthorizeUrl, pasted: &str) -> RedditFuture<()> {
+		let conn = self.conn.clone();
+		Box::new(OAuth::create_installed_app_manual(&self.conn, id, redirect, authorize, pasted).map(move |auth| conn.set_auth(Some(auth))))
+	}
+
+	/// Authorize this app as a web app, with the code reddit redirected the user with. See the
+	/// `auth` module for how to get it.
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `secret` - The app secret registered on Reddit
+	/// * `redirect` - The app redirect URI registered on Reddit
+	/// * `code` - The code reddit redirected the user with
+	pub fn authorize_web_app(&self, id: &str, secret: &str, redirect: &str, code: &str) -> RedditFuture<()> {
+		let conn = self.conn.clone();
+		Box::new(OAuth::create_web_app(&self.conn, id, secret, redirect, code).map(move |auth| conn.set_auth(Some(auth))))
+	}
+
+	/// Authorize this app without a user, as a script or web app. Read requests are then sent to
+	/// the OAuth host, which gets a higher ratelimit.
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `secret` - The app secret registered on Reddit
+	pub fn authorize_client_credentials(&self, id: &str, secret: &str) -> RedditFuture<()> {
+		let conn = self.conn.clone();
+		Box::new(OAuth::create_client_credentials(&self.conn, id, secret).map(move |auth| conn.set_auth(Some(auth))))
+	}
+
+	/// Authorize this app without a user, as an installed app. Read requests are then sent to the
+	/// OAuth host, which gets a higher ratelimit.
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `device_id` - A unique id of 20 to 30 characters for the device the app is installed on,
+	/// or `DO_NOT_TRACK_THIS_DEVICE`
+	pub fn authorize_installed_client(&self, id: &str, device_id: &str) -> RedditFuture<()> {
+		let conn = self.conn.clone();
+		Box::new(OAuth::create_installed_client(&self.conn, id, device_id).map(move |auth| conn.set_auth(Some(auth))))
+	}
+
+	/// Restores the authorization info saved in the token store of the connection, such as when
+	/// the program starts. A saved token that has expired or is about to expire is refreshed with
+	/// `OAuth::refresh`, and the refreshed authorization info is saved again. Restoring is best
+	/// effort: if nothing was saved yet, or the saved info can't be loaded or refreshed, the error
+	/// is logged and the future resolves to false, leaving the app unauthorized so it can be
+	/// authorized as usual. The future only fails if the connection has no token store.
+	pub fn restore_auth(&self) -> RedditFuture<bool> {
+		let saved = match self.conn.token_store() {
+			Some(store) => store.load(),
+			None => return Box::new(future::err(format_err!("The connection has no token store"))),
+		};
+		let auth = match saved {
+			Ok(Some(auth)) => auth,
+			Ok(None) => return Box::new(future::ok(false)),
+			Err(e) => {
+				warn!("Failed to load saved authorization info: {}", e);
+				return Box::new(future::ok(false));
+			}
+		};
+
+		// A token that is still valid can be used as it is
+		if !auth.needs_refresh() {
+			self.conn.set_auth(Some(auth));
+			return Box::new(future::ok(true));
+		}
+		if !auth.can_refresh() {
+			warn!("Saved authorization info has expired and can't be refreshed");
+			return Box::new(future::ok(false));
+		}
+
+		let conn = self.conn.clone();
+		Box::new(auth.refresh(&self.conn).then(move |refreshed| {
+			match refreshed {
+				Ok(auth) => {
+					conn.set_auth(Some(auth));
+					Ok(true)
+				}
+				Err(e) => {
+					warn!("Failed to refresh saved authorization info: {}", e);
+					Ok(false)
+				}
+			}
+		}))
+	}
+}
@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+
+use failure::Error;
+use futures::{future, stream, Future, Stream};
+use json::Value;
+
+use app::AsyncApp;
+use data::{Comment, Listing};
+use net::form::Form;
+use net::RedditFuture;
+use RedditError;
+
+impl AsyncApp {
+	/// Comment on a thing. The `thing` can be a post, a comment, or a private message
+	/// If reddit throttles the comment, it is resubmitted when throttle waiting is enabled with
+	/// `set_throttle_wait`, and fails with `RedditError::Ratelimited` otherwise.
+	/// # Arguments
+	/// * `text` - The body of the comment
+	/// * `thing` - Fullname of the thing to comment on
+	pub fn comment(&self, text: &str, thing: &str) -> RedditFuture<()> {
+		let req = Form::new().param("text", text).param("thing_id", thing).param("api_type", "json").post(&self.conn.oauth_url("/api/comment"));
+
+		Box::new(self.conn.run_submit_request(req).map(|_| ()))
+	}
+
+	/// Load more comments from a comment tree that is not completely loaded. This function at the moment can only be called
+	/// internally due to requiring `morechildren_id` that is not available in the `Thread` type.
+	/// # Arguments
+	/// * `link_id` - The id of the post that has the comments that are being loaded
+	/// * `morechildren_id` - The id of the morechildren object that is being loaded
+	/// * `comments` - Slice of `&str`s that are the ids of the comments to be loaded
+	pub fn more_children(&self, link_id: &str, morechildren_id: &str, comments: &[&str]) -> RedditFuture<Listing<Comment>> {
+		let link_id = if !link_id.starts_with("t3_") { format!("t3_{}", link_id) } else { link_id.to_string() };
+		let morechildren_id = morechildren_id.to_string();
+
+		let limit = 5;
+		// Break requests into chunks of `limit`
+		let mut chunks: Vec<String> = Vec::new();
+		let mut chunk_buf = String::new();
+		for (i, id) in comments.iter().enumerate() {
+			if i != 0 && i % limit == 0 {
+				chunk_buf.pop(); // Removes trailing comma
+				chunks.push(chunk_buf);
+				chunk_buf = String::new();
+			}
+
+			chunk_buf.push_str(&format!("{},", id));
+		}
+		chunk_buf.pop(); // Removes trailing comma on unfinished chunk
+		chunks.push(chunk_buf);
+
+		trace!("Chunks are {:?}", chunks);
+
+		let app = self.clone();
+		// Chunks are loaded one after another so the ratelimiting can space them out
+		let lists = stream::iter_ok::<_, Error>(chunks)
+			.and_then(move |chunk| {
+				trace!("Getting more children {} from {}", chunk, link_id);
+
+				let req = Form::new()
+					.param("children", &chunk)
+					.param("link_id", &link_id)
+					.param("id", &morechildren_id)
+					.param("api_type", "json")
+					.post(&app.conn.read_url("/api/morechildren/.json"));
+
+				let app = app.clone();
+				let link_id = link_id.clone();
+				app.conn.run_read_request(req).and_then(move |data| {
+					trace!("Scanning {}", data);
+
+					app.comments_from_value(data["json"]["data"]["things"].clone(), &link_id)
+				})
+			})
+			.collect();
+
+		Box::new(lists.map(|mut lists| {
+			// Flatten the vec of listings
+			let mut final_list = VecDeque::new();
+			for list in &mut lists {
+				final_list.append(&mut list.children);
+			}
+			let mut listing: Listing<Comment> = Listing::new();
+
+			for comment in final_list {
+				listing.insert_comment(comment);
+			}
+
+			listing
+		}))
+	}
+
+	/// Sticky a post in a subreddit. Does nothing if the post is already stickied
+	/// # Arguments
+	/// * `sticky` - boolean value. True to set post as sticky, false to unset post as sticky
+	/// * `slot` - Optional slot number to fill (can only be 1 or 2, and will error otherwise)
+	/// * `id` - _fullname_ of the post to sticky
+	pub fn set_sticky(&self, sticky: bool, slot: Option<i32>, id: &str) -> RedditFuture<()> {
+		let mut form = Form::new().param("state", if sticky { "1" } else { "0" });
+
+		if let Some(num) = slot {
+			if num != 1 && num != 2 {
+				return Box::new(future::err(Error::from(RedditError::BadRequest {
+					request: "Sticky's are limited to slots 1 and 2".to_string(),
+					status: 400,
+					response: "not sent".to_string(),
+				})));
+			}
+			form = form.param("num", &num.to_string());
+		}
+
+		let req = form.param("id", id).post(&self.conn.oauth_url("/api/set_subreddit_sticky/.json"));
+
+		Box::new(self.conn.run_auth_request(req).map(|_| ()))
+	}
+
+	/// Submit a self post
+	/// If reddit throttles the post, it is resubmitted when throttle waiting is enabled with
+	/// `set_throttle_wait`, and fails with `RedditError::Ratelimited` otherwise.
+	/// # Arguments
+	/// * `sub` - Name of the subreddit to submit a post to
+	/// * `title` - Title of the post
+	/// * `text` - Body of the post
+	/// * `sendreplies` - Whether replies should be forwarded to the inbox of the submitter
+	/// # Returns
+	/// A future resolving to reddit's json response to the submission
+	pub fn submit_self(&self, sub: &str, title: &str, text: &str, sendreplies: bool) -> RedditFuture<Value> {
+		let req = Form::new()
+			.param("sr", sub)
+			.param("kind", "self")
+			.param("title", title)
+			.param("text", text)
+			.param("sendreplies", if sendreplies { "true" } else { "false" })
+			.param("api_type", "json")
+			.post(&self.conn.oauth_url("/api/submit/.json"));
+
+		self.conn.run_submit_request(req)
+	}
+}
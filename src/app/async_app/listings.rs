@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use failure::{err_msg, Error};
+use futures::{future, stream, Future, Stream};
+use hyper::{Body, Request};
+use json::Value;
+use url::Url;
+
+use app::AsyncApp;
+use data::{Comment, Listing, Post};
+use net::{uri_params_from_map, RedditFuture, RedditStream};
+use Sort;
+
+impl AsyncApp {
+	/// Loads a post along with its comment tree
+	/// # Arguments
+	/// * `fullame` - fullname of the thing
+	pub fn load_post(&self, fullname: &str) -> RedditFuture<Post> {
+		let req = Request::get(self.conn.read_url(&format!("/by_id/{}/.json", fullname))).body(Body::empty()).unwrap();
+
+		let app = self.clone();
+		Box::new(self.conn.run_read_request(req).and_then(move |response| {
+			let id = response["data"]["children"][0]["data"]["id"].as_str().unwrap_or_default().to_string();
+			app.get_comment_tree(&id).and_then(move |tree| Post::from_value_with(&response, |_| Ok(tree)))
+		}))
+	}
+
+	/// Get the posts in a subreddit sorted in a specific way
+	/// # Arguments
+	/// * `sub` - Name of subreddit to query
+	/// * `sort` - Sort method of query
+	/// # Returns
+	/// A future resolving to a json listing of posts
+	pub fn get_posts(&self, sub: &str, sort: Sort) -> RedditFuture<Value> {
+		let url = match Url::parse_with_params(&self.conn.read_url(&format!("/r/{}/.json", sub)), sort.param()) {
+			Ok(url) => url,
+			Err(e) => return Box::new(future::err(e.into())),
+		};
+		let req = Request::get(url.into_string()).body(Body::empty()).unwrap();
+
+		self.conn.run_read_request(req)
+	}
+
+	/// Get a stream of all comments in order of being posted
+	/// # Arguments
+	/// * `sub` - Name of the subreddit to pull comments from. Can be 'all' to pull from all of reddit
+	pub fn create_comment_stream(&self, sub: &str) -> RedditStream<Comment> {
+		let app = self.clone();
+		let sub = sub.to_string();
+
+		let listings = stream::unfold(None, move |last: Option<String>| {
+			Some(app.get_recent_comments(&sub, Some(500), last.as_ref().map(|s| s.as_str())).map(move |listing| {
+				let last = listing.children.front().map(|comment| comment.name.clone()).or(last);
+				(listing, last)
+			}))
+		});
+
+		Box::new(listings.map(|listing| stream::iter_ok(listing.children)).flatten())
+	}
+
+	/// Gets the most recent comments in a subreddit. This function is also usually called internally but
+	/// can be called if a one time retrieval of recent comments from a subreddit is necessary
+	/// # Arguments
+	/// * `sub` - Subreddit to load recent comments from
+	/// * `limit` - Optional limit to amount of comments loaded
+	/// * `before` - Optional comment to be the starting point for the next comments loaded
+	/// # Returns
+	/// A future resolving to a listing of comments that should be flat (no replies)
+	pub fn get_recent_comments(&self, sub: &str, limit: Option<i32>, before: Option<&str>) -> RedditFuture<Listing<Comment>> {
+		let limit_str;
+		let mut params: HashMap<&str, &str> = HashMap::new();
+		if let Some(limit) = limit {
+			limit_str = limit.to_string();
+			params.insert("limit", &limit_str);
+		}
+		if let Some(ref before) = before {
+			params.insert("before", before);
+		}
+
+		let uri = match uri_params_from_map(&self.conn.read_url(&format!("/r/{}/comments.json", sub)), &params) {
+			Ok(uri) => uri,
+			Err(e) => return Box::new(future::err(e)),
+		};
+		let req = Request::get(uri).body(Body::empty()).unwrap();
+
+		let app = self.clone();
+		Box::new(self.conn.run_read_request(req).and_then(move |resp| app.comments_from_value(resp["data"]["children"].clone(), "")))
+	}
+
+	/// Loads the comment tree of a post, returning a listing of the Comment enum, which can be
+	/// either Loaded or NotLoaded
+	/// # Arguments
+	/// * `post` - The name of the post to retrieve the tree from
+	/// # Returns
+	/// A future resolving to a fully populated listing of commments (no `more` values)
+	pub fn get_comment_tree(&self, post: &str) -> RedditFuture<Listing<Comment>> {
+		// TODO add sorting and shit
+
+		let url = match Url::parse_with_params(&self.conn.read_url(&format!("/comments/{}/.json", post)), &[("limit", "2147483648"), ("depth", "2147483648")]) {
+			Ok(url) => url,
+			Err(e) => return Box::new(future::err(e.into())),
+		};
+		let req = Request::get(url.into_string()).body(Body::empty()).unwrap();
+
+		let app = self.clone();
+		let post = post.to_string();
+		Box::new(self.conn.run_read_request(req).and_then(move |data| app.comments_from_value(data[1]["data"]["children"].clone(), &post)))
+	}
+
+	/// Parses a listing of comments from json, first loading all comments in it that are not
+	/// loaded yet.
+	pub(crate) fn comments_from_value(&self, data: Value, post_id: &str) -> RedditFuture<Listing<Comment>> {
+		let app = self.clone();
+		let post_id = post_id.to_string();
+		let more_post_id = post_id.clone();
+
+		// More objects are loaded one after another so the ratelimiting can space them out
+		let loaded = stream::iter_ok::<_, Error>(Listing::more_ids(&data))
+			.and_then(move |(more_id, more)| {
+				let more = more.iter().map(|id| id.as_str()).collect::<Vec<&str>>();
+				app.more_children(&more_post_id, &more_id, &more).map(move |listing| (more_id, listing))
+			})
+			.collect();
+
+		Box::new(loaded.and_then(move |loaded| {
+			let mut loaded: HashMap<String, Listing<Comment>> = loaded.into_iter().collect();
+			Listing::from_value_with(&data, &post_id, &mut |_, more_id, _| loaded.remove(more_id).ok_or_else(|| err_msg(format!("Comments of more object {} were not loaded", more_id))))
+		}))
+	}
+}
@@ -0,0 +1,20 @@
+use futures::Future;
+
+use app::AsyncApp;
+use net::form::Form;
+use net::RedditFuture;
+
+impl AsyncApp {
+	/// Send a private message to a user
+	/// If reddit throttles the message, it is resubmitted when throttle waiting is enabled with
+	/// `set_throttle_wait`, and fails with `RedditError::Ratelimited` otherwise.
+	/// # Arguments
+	/// * `to` - Name of the user to send a message to
+	/// * `subject` - Subject of the message
+	/// * `body` - Body of the message
+	pub fn message(&self, to: &str, subject: &str, body: &str) -> RedditFuture<()> {
+		let req = Form::new().param("to", to).param("subject", subject).param("text", body).param("api_type", "json").post(&self.conn.oauth_url("/api/compose/.json"));
+
+		Box::new(self.conn.run_submit_request(req).map(|_| ()))
+	}
+}
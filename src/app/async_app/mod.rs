@@ -0,0 +1,95 @@
+mod account;
+mod auth;
+mod links;
+mod listings;
+mod messages;
+mod raw;
+mod users;
+
+use std::time::Duration;
+
+use failure::Error;
+
+use net::endpoint::Endpoint;
+use net::ratelimit::{RateLimiter, RatelimitStatus};
+use net::timeout::Timeouts;
+use net::{Connection, LimitMethod, RedditFuture};
+
+/// A reddit object that doesn't block. Every method returns a future (or a stream) that has to be
+/// run on an executor, such as a tokio runtime.
+/// ## Usage:
+/// To create a new instance, use `AsyncApp::new()` or `App::builder()`
+#[derive(Clone)]
+pub struct AsyncApp {
+	pub(crate) conn: Connection,
+}
+
+impl AsyncApp {
+	/// Create a new reddit instance
+	/// # Arguments
+	/// * `appname` - Unique app name
+	/// * `appversion` - App version
+	/// * `appauthor` - Auther of the app
+	/// # Returns
+	/// A new reddit object
+	pub fn new(appname: &str, appversion: &str, appauthor: &str) -> Result<AsyncApp, Error> {
+		Ok(AsyncApp { conn: Connection::new(appname, appversion, appauthor)? })
+	}
+
+	/// Create a reddit instance that sends its requests through an existing connection
+	pub fn from_connection(conn: Connection) -> AsyncApp {
+		AsyncApp { conn }
+	}
+
+	/// Returns the connection this reddit instance sends its requests through
+	pub fn connection(&self) -> &Connection {
+		&self.conn
+	}
+
+	/// Calls an endpoint, such as one orca doesn't have a method for yet
+	/// # Arguments
+	/// * `endpoint` - The endpoint to call
+	/// # Returns
+	/// A future resolving to the parsed response of the endpoint
+	pub fn execute<E: Endpoint + Send + 'static>(&self, endpoint: E) -> RedditFuture<E::Response> {
+		self.conn.execute(endpoint)
+	}
+
+	/// Returns a reddit instance sharing everything with this one except the timeouts of its
+	/// requests, for overriding the timeouts of single calls
+	/// # Arguments
+	/// * `timeouts` - The timeouts for requests made with the returned instance
+	pub fn with_timeouts(&self, timeouts: Timeouts) -> AsyncApp {
+		AsyncApp::from_connection(self.conn.with_timeouts(timeouts))
+	}
+
+	/// Sets the method to use for ratelimiting.
+	/// # Arguments
+	/// * `limit` - The method to use for ratelimiting
+	pub fn set_ratelimiting(&self, limit: LimitMethod) {
+		self.conn.set_limit(limit);
+	}
+
+	/// Sets the rate limiter deciding how long to wait before each request, such as a
+	/// `TokenBucketLimiter` shared with other apps using the same OAuth client.
+	/// # Arguments
+	/// * `limiter` - The rate limiter to use
+	pub fn set_ratelimiter<R: RateLimiter + 'static>(&self, limiter: R) {
+		self.conn.set_ratelimiter(limiter);
+	}
+
+	/// Returns the ratelimit budget as reported by the last response, with the requests used and
+	/// remaining and when the ratelimit period resets
+	pub fn ratelimit_status(&self) -> RatelimitStatus {
+		self.conn.ratelimit_status()
+	}
+
+	/// Sets whether submissions (posts, comments and messages) that reddit throttles are resubmitted
+	/// automatically once the wait it asks for has passed.
+	/// # Arguments
+	/// * `max_wait` - The longest total time to wait for a single submission, or None to return a
+	/// `RedditError::Ratelimited` error with the wait instead
+	pub fn set_throttle_wait(&self, max_wait: Option<Duration>) {
+		self.conn.set_throttle_wait(max_wait);
+	}
+}
@@ -0,0 +1,86 @@
+use failure::Error;
+use hyper::Method;
+use json::Value;
+
+use app::AsyncApp;
+use net::endpoint::Endpoint;
+use net::form::Form;
+use net::RedditFuture;
+
+// An endpoint called with a raw path, authorized if the connection is
+struct RawEndpoint {
+	method: Method,
+	path: String,
+	query: Vec<(String, String)>,
+	form: Option<Form>,
+	auth: bool,
+}
+
+impl RawEndpoint {
+	fn new(app: &AsyncApp, method: Method, path: &str, query: &[(&str, &str)], form: Option<Form>) -> RawEndpoint {
+		let mut params = vec![("raw_json".to_string(), "1".to_string())];
+		params.extend(query.iter().map(|&(name, value)| (name.to_string(), value.to_string())));
+
+		RawEndpoint {
+			method,
+			path: if path.starts_with('/') { path.to_string() } else { format!("/{}", path) },
+			query: params,
+			form,
+			auth: app.conn.auth().is_some(),
+		}
+	}
+}
+
+impl Endpoint for RawEndpoint {
+	type Response = Value;
+
+	fn method(&self) -> Method {
+		self.method.clone()
+	}
+
+	fn path(&self) -> &str {
+		&self.path
+	}
+
+	fn query(&self) -> Vec<(String, String)> {
+		self.query.clone()
+	}
+
+	fn form(&self) -> Option<Form> {
+		self.form.clone()
+	}
+
+	fn requires_auth(&self) -> bool {
+		self.auth
+	}
+
+	fn parse(&self, response: Value) -> Result<Value, Error> {
+		Ok(response)
+	}
+}
+
+impl AsyncApp {
+	/// Sends a GET request to any path of the reddit API, for endpoints orca doesn't have a method
+	/// for. If the app is authorized, the request is sent to the OAuth host with the token of the
+	/// app, refreshing it first if needed, and otherwise to the public host. `raw_json=1` is added
+	/// to the query, so text in the response isn't HTML-escaped.
+	/// # Arguments
+	/// * `path` - The path of the endpoint, such as `/api/v1/me/karma`
+	/// * `query` - Query parameters, not encoded
+	/// # Returns
+	/// A future resolving to the json response
+	pub fn get_raw(&self, path: &str, query: &[(&str, &str)]) -> RedditFuture<Value> {
+		self.conn.execute(RawEndpoint::new(self, Method::GET, path, query, None))
+	}
+
+	/// Sends a POST request with a form body to any path of the reddit API, for endpoints orca
+	/// doesn't have a method for. The request is sent like the ones of `get_raw`.
+	/// # Arguments
+	/// * `path` - The path of the endpoint, such as `/api/save`
+	/// * `form` - The parameters of the request
+	/// # Returns
+	/// A future resolving to the json response
+	pub fn post_raw(&self, path: &str, form: Form) -> RedditFuture<Value> {
+		self.conn.execute(RawEndpoint::new(self, Method::POST, path, &[], Some(form)))
+	}
+}
@@ -0,0 +1,18 @@
+use hyper::{Body, Request};
+use json::Value;
+
+use app::AsyncApp;
+use net::RedditFuture;
+
+impl AsyncApp {
+	/// Gets information about a user that is not currently authorized
+	/// # Arguments
+	/// * `name` - username of the user to query
+	/// # Returns
+	/// A future resolving to a json value containing the user info
+	pub fn get_user(&self, name: &str) -> RedditFuture<Value> {
+		let req = Request::get(self.conn.read_url(&format!("/user/{}/about/.json", name))).body(Body::empty()).unwrap();
+
+		self.conn.run_read_request(req)
+	}
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use net::auth::OAuth;
+use net::auth::AuthorizeUrl;
 use {App, ResponseGenFn, Scopes};
 
 use failure::Error;
@@ -13,9 +13,7 @@ impl App {
 	/// * `username` - The username of the user to authorize as
 	/// * `password` - The password of the user to authorize as
 	pub fn authorize_script(&mut self, id: &str, secret: &str, username: &str, password: &str) -> Result<(), Error> {
-		let auth = OAuth::create_script(&self.conn, id, secret, username, password)?;
-		self.conn.auth = Some(auth);
-		Ok(())
+		self.run(self.inner.authorize_script(id, secret, username, password))
 	}
 
 	/// Authorize this app as an installed app
@@ -31,8 +29,67 @@ impl App {
 	/// * `scopes` - A reference to a Scopes instance representing the capabilites you are requesting
 	/// as an installed app.
 	pub fn authorize_installed_app<I: Into<Option<Arc<ResponseGenFn>>>>(&mut self, id: &str, redirect: &str, response_gen: I, scopes: &Scopes) -> Result<(), Error> {
-		let auth = OAuth::create_installed_app(&self.conn, id, redirect, response_gen, scopes)?;
-		self.conn.auth = Some(auth);
-		Ok(())
+		self.run(self.inner.authorize_installed_app(id, redirect, response_gen, scopes))
+	}
+
+	/// Creates the url to send a user to for authorizing this app as an installed or web app
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `redirect` - The app redirect URI registered on Reddit
+	/// * `scopes` - A reference to a Scopes instance representing the capabilites you are requesting
+	/// * `permanent` - Whether to request a refresh token, so the app stays authorized after the
+	/// first token expires
+	pub fn authorize_url(&self, id: &str, redirect: &str, scopes: &Scopes, permanent: bool) -> Result<AuthorizeUrl, Error> {
+		self.inner.authorize_url(id, redirect, scopes, permanent)
+	}
+
+	/// Authorize this app as an installed app without opening a browser or starting a server, for
+	/// machines without a desktop. Show the url made by `authorize_url` to the user, and pass what
+	/// they paste back once they allowed the app.
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `redirect` - The app redirect URI registered on Reddit
+	/// * `authorize` - The url the user was sent to, made with `authorize_url`
+	/// * `pasted` - The url the user was redirected to, or its query, as pasted by the user
+	pub fn authorize_installed_app_manual(&mut self, id: &str, redirect: &str, authorize: &AuthorizeUrl, pasted: &str) -> Result<(), Error> {
+		self.run(self.inner.authorize_installed_app_manual(id, redirect, authorize, pasted))
+	}
+
+	/// Authorize this app as a web app, with the code reddit redirected the user with. See the
+	/// `auth` module for how to get it.
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `secret` - The app secret registered on Reddit
+	/// * `redirect` - The app redirect URI registered on Reddit
+	/// * `code` - The code reddit redirected the user with
+	pub fn authorize_web_app(&mut self, id: &str, secret: &str, redirect: &str, code: &str) -> Result<(), Error> {
+		self.run(self.inner.authorize_web_app(id, secret, redirect, code))
+	}
+
+	/// Authorize this app without a user, as a script or web app. Read requests are then sent to
+	/// the OAuth host, which gets a higher ratelimit.
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `secret` - The app secret registered on Reddit
+	pub fn authorize_client_credentials(&mut self, id: &str, secret: &str) -> Result<(), Error> {
+		self.run(self.inner.authorize_client_credentials(id, secret))
+	}
+
+	/// Authorize this app without a user, as an installed app. Read requests are then sent to the
+	/// OAuth host, which gets a higher ratelimit.
+	/// # Arguments
+	/// * `id` - The app id registered on Reddit
+	/// * `device_id` - A unique id of 20 to 30 characters for the device the app is installed on,
+	/// or `DO_NOT_TRACK_THIS_DEVICE`
+	pub fn authorize_installed_client(&mut self, id: &str, device_id: &str) -> Result<(), Error> {
+		self.run(self.inner.authorize_installed_client(id, device_id))
+	}
+
+	/// Restores the authorization info saved in the token store, refreshing its token if it has
+	/// expired. Returns false, leaving the app unauthorized, if nothing was saved yet or the saved
+	/// info couldn't be restored. This is done when the app is built if a token store is set with
+	/// `AppBuilder::token_store`.
+	pub fn restore_auth(&self) -> Result<bool, Error> {
+		self.run(self.inner.restore_auth())
 	}
 }
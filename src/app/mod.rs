@@ -1,19 +1,43 @@
 mod account;
+mod async_app;
 mod auth;
 mod links;
 mod listings;
 mod messages;
+mod raw;
 mod users;
 
+pub use self::async_app::AsyncApp;
+
+use std::sync::Arc;
+use std::time::Duration;
+
 use failure::Error;
+use futures::sync::oneshot;
+use futures::Future;
+use tokio::runtime::Runtime;
 
-use net::{Connection, LimitMethod};
+use net::cache::ResponseCache;
+use net::endpoint::Endpoint;
+use net::metrics::Metrics;
+use net::middleware::Middleware;
+use net::proxy::Proxy;
+use net::ratelimit::{RateLimiter, RatelimitStatus};
+use net::retry::RetryPolicy;
+use net::timeout::Timeouts;
+use net::tokenstore::TokenStore;
+use net::transport::Transport;
+use net::useragent::UserAgent;
+use net::{BaseUrls, Connection, ConnectionBuilder, LimitMethod};
 
-/// A reddit object
+/// A reddit object. Every method blocks until it's finished, by running the equivalent method
+/// of `AsyncApp` to completion on a runtime owned by the app. It can be shared between threads,
+/// and calls from different threads run concurrently.
 /// ## Usage:
 /// To create a new instance, use `Reddit::new()`
 pub struct App {
-	pub(crate) conn: Connection,
+	pub(crate) inner: AsyncApp,
+	runtime: Arc<Runtime>,
 }
 
 impl App {
@@ -25,13 +49,258 @@ impl App {
 	/// # Returns
 	/// A new reddit object
 	pub fn new(appname: &str, appversion: &str, appauthor: &str) -> Result<App, Error> {
-		Ok(App { conn: Connection::new(appname, appversion, appauthor)? })
+		App::from_async(AsyncApp::new(appname, appversion, appauthor)?)
+	}
+
+	/// Create a blocking reddit instance from a non-blocking one. Both share the same connection.
+	pub fn from_async(inner: AsyncApp) -> Result<App, Error> {
+		Ok(App { inner, runtime: Arc::new(Runtime::new()?) })
+	}
+
+	/// Create a builder for a reddit instance, which allows changing settings of the underlying
+	/// connection
+	/// # Arguments
+	/// * `appname` - Unique app name
+	/// * `appversion` - App version
+	/// * `appauthor` - Auther of the app
+	pub fn builder(appname: &str, appversion: &str, appauthor: &str) -> AppBuilder {
+		AppBuilder { conn: ConnectionBuilder::new(appname, appversion, appauthor) }
 	}
 
 	/// Sets the method to use for ratelimiting.
 	/// # Arguments
 	/// * `limit` - The method to use for ratelimiting
 	pub fn set_ratelimiting(&self, limit: LimitMethod) {
-		self.conn.set_limit(limit);
+		self.inner.set_ratelimiting(limit);
+	}
+
+	/// Sets the rate limiter deciding how long to wait before each request, such as a
+	/// `TokenBucketLimiter` shared with other apps using the same OAuth client.
+	/// # Arguments
+	/// * `limiter` - The rate limiter to use
+	pub fn set_ratelimiter<R: RateLimiter + 'static>(&self, limiter: R) {
+		self.inner.set_ratelimiter(limiter);
+	}
+
+	/// Returns the ratelimit budget as reported by the last response, with the requests used and
+	/// remaining and when the ratelimit period resets
+	pub fn ratelimit_status(&self) -> RatelimitStatus {
+		self.inner.ratelimit_status()
+	}
+
+	/// Sets whether submissions (posts, comments and messages) that reddit throttles are resubmitted
+	/// automatically once the wait it asks for has passed.
+	/// # Arguments
+	/// * `max_wait` - The longest total time to wait for a single submission, or None to return a
+	/// `RedditError::Ratelimited` error with the wait instead
+	pub fn set_throttle_wait(&self, max_wait: Option<Duration>) {
+		self.inner.set_throttle_wait(max_wait);
+	}
+
+	/// Calls an endpoint, such as one orca doesn't have a method for yet
+	/// # Arguments
+	/// * `endpoint` - The endpoint to call
+	/// # Returns
+	/// A result with the parsed response of the endpoint
+	pub fn execute<E: Endpoint + Send + 'static>(&self, endpoint: E) -> Result<E::Response, Error> {
+		self.run(self.inner.execute(endpoint))
+	}
+
+	/// Returns a reddit instance sharing everything with this one except the timeouts of its
+	/// requests, for overriding the timeouts of single calls
+	/// # Arguments
+	/// * `timeouts` - The timeouts for requests made with the returned instance
+	pub fn with_timeouts(&self, timeouts: Timeouts) -> App {
+		App {
+			inner: self.inner.with_timeouts(timeouts),
+			runtime: Arc::clone(&self.runtime),
+		}
+	}
+
+	/// Returns the non-blocking reddit instance this one wraps
+	pub fn as_async(&self) -> &AsyncApp {
+		&self.inner
+	}
+
+	/// Returns the connection this reddit instance sends its requests through
+	pub fn connection(&self) -> &Connection {
+		self.inner.connection()
+	}
+
+	/// Runs a future to completion on the runtime, blocking until it's done. Must not be called
+	/// from within a future running on the runtime.
+	pub(crate) fn run<F>(&self, future: F) -> Result<F::Item, F::Error>
+	where
+		F: Future + Send + 'static,
+		F::Item: Send,
+		F::Error: Send,
+	{
+		oneshot::spawn(future, &self.runtime.executor()).wait()
+	}
+}
+
+/// A builder for a reddit object
+/// ## Usage:
+/// To create a new builder, use `App::builder()`
+pub struct AppBuilder {
+	conn: ConnectionBuilder,
+}
+
+impl AppBuilder {
+	/// Sets the user agent, replacing the one made from the app name, version and author
+	pub fn useragent(mut self, useragent: UserAgent) -> AppBuilder {
+		self.conn = self.conn.useragent(useragent);
+		self
+	}
+
+	/// Sets all base urls at once
+	pub fn base_urls(mut self, urls: BaseUrls) -> AppBuilder {
+		self.conn = self.conn.base_urls(urls);
+		self
+	}
+
+	/// Sets the base url for requests that don't require authorization (`https://www.reddit.com` by default)
+	pub fn public_url(mut self, url: &str) -> AppBuilder {
+		self.conn = self.conn.public_url(url);
+		self
+	}
+
+	/// Sets the base url for requests that require OAuth authorization (`https://oauth.reddit.com` by default)
+	pub fn oauth_url(mut self, url: &str) -> AppBuilder {
+		self.conn = self.conn.oauth_url(url);
+		self
+	}
+
+	/// Sets the base url for requesting access tokens (`https://ssl.reddit.com` by default)
+	pub fn token_url(mut self, url: &str) -> AppBuilder {
+		self.conn = self.conn.token_url(url);
+		self
+	}
+
+	/// Sets the transport requests are sent with. By default a `HyperTransport` is used.
+	pub fn transport<T: Transport + 'static>(mut self, transport: T) -> AppBuilder {
+		self.conn = self.conn.transport(transport);
+		self
+	}
+
+	/// Sets a proxy to send all requests through, including token requests. This only applies to
+	/// the default transport, and is ignored if another transport is set.
+	pub fn proxy(mut self, proxy: Proxy) -> AppBuilder {
+		self.conn = self.conn.proxy(proxy);
+		self
+	}
+
+	/// Sets whether connections are kept open to be reused by later requests (enabled by default).
+	/// This only applies to the default transport.
+	pub fn keep_alive(mut self, keep_alive: bool) -> AppBuilder {
+		self.conn = self.conn.keep_alive(keep_alive);
+		self
+	}
+
+	/// Sets the most idle connections to keep open to a single host (unlimited by default). This
+	/// only applies to the default transport.
+	pub fn pool_max_idle(mut self, max_idle: usize) -> AppBuilder {
+		self.conn = self.conn.pool_max_idle(max_idle);
+		self
+	}
+
+	/// Sets how long an idle connection is kept open (90 seconds by default), or None to keep it
+	/// open until reddit closes it. This only applies to the default transport.
+	pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> AppBuilder {
+		self.conn = self.conn.pool_idle_timeout(timeout);
+		self
+	}
+
+	/// Sets whether to use HTTP/2 when reddit supports it, which sends all requests over a single
+	/// connection (disabled by default). This only applies to the default transport.
+	pub fn http2(mut self, http2: bool) -> AppBuilder {
+		self.conn = self.conn.http2(http2);
+		self
+	}
+
+	/// Sets the amount of threads resolving host names (4 by default). This only applies to the
+	/// default transport.
+	pub fn dns_threads(mut self, threads: usize) -> AppBuilder {
+		self.conn = self.conn.dns_threads(threads);
+		self
+	}
+
+	/// Sets how long requests may take. By default `Timeouts::default()` is used.
+	pub fn timeouts(mut self, timeouts: Timeouts) -> AppBuilder {
+		self.conn = self.conn.timeouts(timeouts);
+		self
+	}
+
+	/// Adds a middleware to the end of the chain every request and response passes through
+	pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> AppBuilder {
+		self.conn = self.conn.middleware(middleware);
+		self
+	}
+
+	/// Sets a cache for responses to GET requests. By default nothing is cached.
+	pub fn cache(mut self, cache: ResponseCache) -> AppBuilder {
+		self.conn = self.conn.cache(cache);
+		self
+	}
+
+	/// Sets a registry to record metrics about requests in. By default no metrics are recorded.
+	pub fn metrics(mut self, metrics: Arc<Metrics>) -> AppBuilder {
+		self.conn = self.conn.metrics(metrics);
+		self
+	}
+
+	/// Sets a store to save the authorization info in whenever it changes. A blocking app restores
+	/// the saved authorization info when it's built, see `App::restore_auth`.
+	pub fn token_store<S: TokenStore + 'static>(mut self, store: S) -> AppBuilder {
+		self.conn = self.conn.token_store(store);
+		self
+	}
+
+	/// Sets the policy for retrying requests that failed with a transient error. By default
+	/// `RetryPolicy::default()` is used.
+	pub fn retry_policy(mut self, retry: RetryPolicy) -> AppBuilder {
+		self.conn = self.conn.retry_policy(retry);
+		self
+	}
+
+	/// Sets the rate limiter deciding how long to wait before each request. By default a
+	/// `SteadyLimiter` is used. To share a limiter between apps, pass the same `Arc` to each of them.
+	pub fn ratelimiter<R: RateLimiter + 'static>(mut self, limiter: R) -> AppBuilder {
+		self.conn = self.conn.ratelimiter(limiter);
+		self
+	}
+
+	/// Sets a callback that is called when the remaining ratelimit budget drops below a threshold
+	/// # Arguments
+	/// * `threshold` - The amount of remaining requests below which the callback is called
+	/// * `callback` - The function to call, with the ratelimit status at that moment
+	pub fn on_low_ratelimit<F: Fn(&RatelimitStatus) + Send + Sync + 'static>(mut self, threshold: i32, callback: F) -> AppBuilder {
+		self.conn = self.conn.on_low_ratelimit(threshold, callback);
+		self
+	}
+
+	/// Makes submissions that reddit throttles wait and resubmit automatically, as long as the
+	/// total wait for a submission stays under `max_wait`. By default throttled submissions fail
+	/// with `RedditError::Ratelimited` instead.
+	pub fn throttle_wait(mut self, max_wait: Duration) -> AppBuilder {
+		self.conn = self.conn.throttle_wait(max_wait);
+		self
+	}
+
+	/// Creates the reddit object. If a token store is set, the authorization info saved in it is
+	/// restored, which sends a request if its token has to be refreshed. Failing to restore it
+	/// doesn't fail the build, and leaves the app unauthorized instead.
+	pub fn build(self) -> Result<App, Error> {
+		let app = App::from_async(self.build_async()?)?;
+		if app.inner.conn.token_store().is_some() {
+			app.restore_auth()?;
+		}
+		Ok(app)
+	}
+
+	/// Creates a non-blocking reddit object. Saved authorization info is not restored, see
+	/// `AsyncApp::restore_auth`.
+	pub fn build_async(self) -> Result<AsyncApp, Error> {
+		Ok(AsyncApp::from_connection(self.conn.build()?))
 	}
 }
@@ -0,0 +1,31 @@
+use failure::Error;
+use json::Value;
+
+use net::form::Form;
+use App;
+
+impl App {
+	/// Sends a GET request to any path of the reddit API, for endpoints orca doesn't have a method
+	/// for. If the app is authorized, the request is sent to the OAuth host with the token of the
+	/// app, refreshing it first if needed, and otherwise to the public host. `raw_json=1` is added
+	/// to the query, so text in the response isn't HTML-escaped.
+	/// # Arguments
+	/// * `path` - The path of the endpoint, such as `/api/v1/me/karma`
+	/// * `query` - Query parameters, not encoded
+	/// # Returns
+	/// A result with the json response
+	pub fn get_raw(&self, path: &str, query: &[(&str, &str)]) -> Result<Value, Error> {
+		self.run(self.inner.get_raw(path, query))
+	}
+
+	/// Sends a POST request with a form body to any path of the reddit API, for endpoints orca
+	/// doesn't have a method for. The request is sent like the ones of `get_raw`.
+	/// # Arguments
+	/// * `path` - The path of the endpoint, such as `/api/save`
+	/// * `form` - The parameters of the request
+	/// # Returns
+	/// A result with the json response
+	pub fn post_raw(&self, path: &str, form: Form) -> Result<Value, Error> {
+		self.run(self.inner.post_raw(path, form))
+	}
+}
@@ -1,5 +1,4 @@
 use failure::Error;
-use hyper::{Body, Request};
 use json::Value;
 
 use App;
@@ -11,8 +10,6 @@ impl App {
 	/// # Returns
 	/// A json value containing the user info
 	pub fn get_user(&self, name: &str) -> Result<Value, Error> {
-		let req = Request::get(format!("https://www.reddit.com/user/{}/about/.json", name)).body(Body::empty()).unwrap();
-
-		self.conn.run_request(req)
+		self.run(self.inner.get_user(name))
 	}
 }
@@ -55,6 +55,16 @@ pub struct Comment {
 
 impl Thing for Comment {
 	fn from_value(val: &Value, app: &App) -> Result<Comment, Error> {
+		Comment::from_value_with(val, &mut |post_id, more_id, more| app.more_children(post_id, more_id, more))
+	}
+}
+
+impl Comment {
+	/// Parses the comment from json, calling `more_children` to load replies that aren't loaded yet
+	pub(crate) fn from_value_with<F>(val: &Value, more_children: &mut F) -> Result<Comment, Error>
+	where
+		F: FnMut(&str, &str, &[&str]) -> Result<Listing<Comment>, Error>,
+	{
 		// nice
 		macro_rules! out {
 			($val:ident) => {
@@ -125,7 +135,7 @@ impl Thing for Comment {
 		};
 		let replies: Listing<Comment> = match val["replies"] {
 			Value::String(_) => Listing::new(),
-			Value::Object(_) => Listing::from_value(&val["replies"]["data"]["children"], &link_id, app).unwrap(),
+			Value::Object(_) => Listing::from_value_with(&val["replies"]["data"]["children"], &link_id, more_children).unwrap(),
 			_ => return Err(err_msg(format!("Unexpected value for \"replies\": {}", val["replies"]))),
 		};
 
@@ -4,7 +4,7 @@ use std::default::Default;
 use json;
 use json::Value;
 
-use data::{Comment, Thing};
+use data::Comment;
 use App;
 
 use errors::ParseError;
@@ -79,13 +79,22 @@ impl Listing<Comment> {
 
 	/// Parses the listing from json, fetching more comments as necessary.
 	pub fn from_value(listing_data: &Value, post_id: &str, app: &App) -> Result<Listing<Comment>, Error> {
+		Listing::from_value_with(listing_data, post_id, &mut |post_id, more_id, more| app.more_children(post_id, more_id, more))
+	}
+
+	/// Parses the listing from json, calling `more_children` with the post id, the id of the more
+	/// object and the ids of the comments to load whenever comments that aren't loaded yet are found.
+	pub(crate) fn from_value_with<F>(listing_data: &Value, post_id: &str, more_children: &mut F) -> Result<Listing<Comment>, Error>
+	where
+		F: FnMut(&str, &str, &[&str]) -> Result<Listing<Comment>, Error>,
+	{
 		let mut listing: Listing<Comment> = Listing::new();
 
 		if let Some(array) = listing_data.as_array() {
 			for item in array {
 				let kind = item["kind"].as_str().unwrap();
 				if kind == "t1" {
-					listing.children.push_back(if let Ok(c) = Comment::from_value(item, app) {
+					listing.children.push_back(if let Ok(c) = Comment::from_value_with(item, more_children) {
 						c
 					} else {
 						return Err(Error::from(ParseError {
@@ -99,7 +108,7 @@ impl Listing<Comment> {
 					if !more.is_empty() {
 						debug!("Need some children {}", json::to_string_pretty(more).unwrap());
 						let more = more.iter().map(|i| i.as_str().unwrap()).collect::<Vec<&str>>();
-						for child in app.more_children(post_id, more_id, &more)? {
+						for child in more_children(post_id, more_id, &more)? {
 							listing.children.push_back(child);
 						}
 						trace!("Successfully got children");
@@ -115,4 +124,27 @@ impl Listing<Comment> {
 			}))
 		}
 	}
+
+	/// Finds all comments in a json listing that are not loaded yet, including those in replies.
+	/// Returns the id of each more object along with the ids of the comments it contains.
+	pub(crate) fn more_ids(listing_data: &Value) -> Vec<(String, Vec<String>)> {
+		let mut ids = Vec::new();
+
+		if let Some(array) = listing_data.as_array() {
+			for item in array {
+				match item["kind"].as_str() {
+					Some("t1") => ids.append(&mut Listing::more_ids(&item["data"]["replies"]["data"]["children"])),
+					Some("more") => {
+						let more = item["data"]["children"].as_array().map(|more| more.iter().filter_map(|i| i.as_str().map(|i| i.to_string())).collect::<Vec<String>>()).unwrap_or_default();
+						if let (false, Some(more_id)) = (more.is_empty(), item["data"]["id"].as_str()) {
+							ids.push((more_id.to_string(), more));
+						}
+					}
+					_ => {}
+				}
+			}
+		}
+
+		ids
+	}
 }
@@ -35,6 +35,16 @@ pub struct Post {
 
 impl Thing for Post {
 	fn from_value(val: &Value, app: &App) -> Result<Post, Error> {
+		Post::from_value_with(val, |id| app.get_comment_tree(id))
+	}
+}
+
+impl Post {
+	/// Parses the post from json, calling `comments` with the id of the post to load its comments
+	pub(crate) fn from_value_with<F>(val: &Value, comments: F) -> Result<Post, Error>
+	where
+		F: FnOnce(&str) -> Result<Listing<Comment>, Error>,
+	{
 		let post = &val["data"]["children"][0]["data"];
 
 		macro_rules! out {
@@ -90,7 +100,7 @@ impl Thing for Post {
 			Some(t) => t,
 			None => out!(val),
 		};
-		let comments = app.get_comment_tree(&id)?;
+		let comments = comments(&id)?;
 
 		Ok(Post {
 			id,
@@ -1,3 +1,8 @@
+use std::time::Duration;
+
+use hyper::StatusCode;
+use json::Value;
+
 /// An enum containing possible errors from a request to reddit
 #[derive(Debug, Fail)]
 pub enum RedditError {
@@ -6,12 +11,114 @@ pub enum RedditError {
 	NotFound {
 		/// The requested resource
 		request: String,
+		/// The HTTP status code of the response
+		status: u16,
 	},
 	/// The requested resource is forbidden
 	#[fail(display = "Requested resource {} is forbidden", request)]
 	Forbidden {
 		/// The requested resource
 		request: String,
+		/// The HTTP status code of the response
+		status: u16,
+	},
+	/// The request was not authorized. This is also returned without sending the request if the
+	/// connection has no token, or the token has expired and can't be refreshed.
+	#[fail(display = "Request {} was not authorized", request)]
+	Unauthorized {
+		/// The request that was sent
+		request: String,
+		/// The HTTP status code of the response
+		status: u16,
+	},
+	/// Reddit refused the request because too many requests were made. This is returned both for
+	/// 429 responses and for `RATELIMIT` errors, such as when commenting too often.
+	#[fail(display = "Ratelimited on request {}: {}", request, message)]
+	Ratelimited {
+		/// The request that was sent
+		request: String,
+		/// The HTTP status code of the response
+		status: u16,
+		/// How long reddit asked to wait before trying again, if it said so
+		wait: Option<Duration>,
+		/// The field of the request the error is about, if any
+		field: Option<String>,
+		/// The explanation given by reddit
+		message: String,
+	},
+	/// The subreddit doesn't allow this action, such as when submitting to a subreddit that is
+	/// restricted or where the user is banned
+	#[fail(display = "Subreddit does not allow request {}: {}", request, message)]
+	SubredditNotAllowed {
+		/// The request that was sent
+		request: String,
+		/// The HTTP status code of the response
+		status: u16,
+		/// The field of the request the error is about, if any
+		field: Option<String>,
+		/// The explanation given by reddit
+		message: String,
+	},
+	/// The thing being replied to is locked or archived
+	#[fail(display = "Thread is locked for request {}: {}", request, message)]
+	ThreadLocked {
+		/// The request that was sent
+		request: String,
+		/// The HTTP status code of the response
+		status: u16,
+		/// The field of the request the error is about, if any
+		field: Option<String>,
+		/// The explanation given by reddit
+		message: String,
+	},
+	/// A field of the request, such as the title of a post, is too long
+	#[fail(display = "Field {:?} is too long in request {}: {}", field, request, message)]
+	TooLong {
+		/// The request that was sent
+		request: String,
+		/// The HTTP status code of the response
+		status: u16,
+		/// The field of the request that is too long
+		field: Option<String>,
+		/// The explanation given by reddit
+		message: String,
+	},
+	/// The thing being replied to has been deleted
+	#[fail(display = "Thing was deleted for request {}: {}", request, message)]
+	Deleted {
+		/// The request that was sent
+		request: String,
+		/// The HTTP status code of the response
+		status: u16,
+		/// The field of the request the error is about, if any
+		field: Option<String>,
+		/// The explanation given by reddit
+		message: String,
+	},
+	/// Reddit returned an error in the `json.errors` array of the response that doesn't have a
+	/// more specific variant
+	#[fail(display = "Reddit returned error {} for request {}: {}", code, request, message)]
+	ApiError {
+		/// The request that was sent
+		request: String,
+		/// The HTTP status code of the response
+		status: u16,
+		/// The error code given by reddit, such as `NO_TEXT`
+		code: String,
+		/// The field of the request the error is about, if any
+		field: Option<String>,
+		/// The explanation given by reddit
+		message: String,
+	},
+	/// Reddit failed to handle the request because of a problem on its side
+	#[fail(display = "\nSent request {}, got server error {} with response {}\n", request, status, response)]
+	ServerError {
+		/// The request that was sent
+		request: String,
+		/// The HTTP status code of the response
+		status: u16,
+		/// The body of the response that was recieved
+		response: String,
 	},
 	/// Recieved a response that was unexpected
 	#[fail(display = "\nSent request {}, got unexpected reponse {}\n", request, response)]
@@ -26,14 +133,161 @@ pub enum RedditError {
 	BadRequest {
 		/// The request that was sent
 		request: String,
+		/// The HTTP status code of the response, or 400 if the request was found to be incorrect
+		/// before it was sent
+		status: u16,
 		/// The response that was recieved
 		response: String,
 	},
+	/// A request took longer than one of its timeouts allowed
+	#[fail(display = "Request {} timed out after {:?} ({:?} timeout)", request, after, kind)]
+	Timeout {
+		/// The request that was sent
+		request: String,
+		/// Which timeout expired
+		kind: TimeoutKind,
+		/// The duration of the timeout that expired
+		after: Duration,
+	},
+	/// A request was cancelled with a `CancelHandle`
+	#[fail(display = "Request was cancelled")]
+	Cancelled,
 	/// Authorization failed
 	#[fail(display = "Failed to authorize")]
 	AuthError,
 }
 
+/// The timeouts a request can exceed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutKind {
+	/// Establishing a connection took too long
+	Connect,
+	/// Waiting for (part of) the response took too long
+	Read,
+	/// The whole request took too long
+	Total,
+}
+
+impl RedditError {
+	/// Returns the HTTP status code of the response that caused this error, if there was one
+	pub fn status(&self) -> Option<u16> {
+		match *self {
+			RedditError::NotFound { status, .. }
+			| RedditError::Forbidden { status, .. }
+			| RedditError::Unauthorized { status, .. }
+			| RedditError::Ratelimited { status, .. }
+			| RedditError::SubredditNotAllowed { status, .. }
+			| RedditError::ThreadLocked { status, .. }
+			| RedditError::TooLong { status, .. }
+			| RedditError::Deleted { status, .. }
+			| RedditError::ApiError { status, .. }
+			| RedditError::ServerError { status, .. }
+			| RedditError::BadRequest { status, .. } => Some(status),
+			RedditError::BadResponse { .. } | RedditError::Timeout { .. } | RedditError::Cancelled | RedditError::AuthError => None,
+		}
+	}
+
+	/// Returns the field of the request this error is about, if reddit named one
+	pub fn field(&self) -> Option<&str> {
+		match *self {
+			RedditError::Ratelimited { ref field, .. }
+			| RedditError::SubredditNotAllowed { ref field, .. }
+			| RedditError::ThreadLocked { ref field, .. }
+			| RedditError::TooLong { ref field, .. }
+			| RedditError::Deleted { ref field, .. }
+			| RedditError::ApiError { ref field, .. } => field.as_ref().map(|field| field.as_str()),
+			_ => None,
+		}
+	}
+
+	/// Creates an error from an unsuccessful response
+	/// # Arguments
+	/// * `request` - Description of the request that was sent
+	/// * `status` - Status of the response
+	/// * `wait` - How long reddit asked to wait before trying again, if it did
+	/// * `body` - Body of the response
+	pub(crate) fn from_status(request: String, status: StatusCode, wait: Option<Duration>, body: String) -> RedditError {
+		let code = status.as_u16();
+		match status {
+			StatusCode::NOT_FOUND => RedditError::NotFound { request, status: code },
+			StatusCode::FORBIDDEN => RedditError::Forbidden { request, status: code },
+			StatusCode::UNAUTHORIZED => RedditError::Unauthorized { request, status: code },
+			StatusCode::TOO_MANY_REQUESTS => RedditError::Ratelimited {
+				request,
+				status: code,
+				wait,
+				field: None,
+				message: body,
+			},
+			_ if status.is_server_error() => RedditError::ServerError { request, status: code, response: body },
+			_ => RedditError::BadRequest { request, status: code, response: body },
+		}
+	}
+
+	/// Creates an error from the first entry of the `json.errors` array of a response, or None if
+	/// it has no errors. Each entry has the form `[code, message, field]`.
+	/// # Arguments
+	/// * `request` - Description of the request that was sent
+	/// * `status` - Status of the response
+	/// * `value` - The parsed body of the response
+	pub(crate) fn from_json_errors(request: String, status: StatusCode, value: &Value) -> Option<RedditError> {
+		let errors = value["json"]["errors"].as_array()?;
+		let error = errors.first()?;
+		if errors.len() > 1 {
+			warn!("Reddit returned multiple errors for {}: {}", request, value["json"]["errors"]);
+		}
+
+		let code = error[0].as_str().unwrap_or("UNKNOWN").to_string();
+		let message = error[1].as_str().unwrap_or("").to_string();
+		let field = error[2].as_str().map(|field| field.to_string());
+		let status = status.as_u16();
+
+		Some(match code.as_str() {
+			"RATELIMIT" => RedditError::Ratelimited {
+				request,
+				status,
+				wait: ratelimit_wait(&value["json"]["ratelimit"]).or_else(|| parse_ratelimit_wait(&message)),
+				field,
+				message,
+			},
+			"SUBREDDIT_NOTALLOWED" | "SUBREDDIT_NOEXIST" | "NO_SELFS" | "NO_LINKS" => RedditError::SubredditNotAllowed { request, status, field, message },
+			"THREAD_LOCKED" | "TOO_OLD" => RedditError::ThreadLocked { request, status, field, message },
+			"TOO_LONG" => RedditError::TooLong { request, status, field, message },
+			"DELETED_COMMENT" | "DELETED_LINK" => RedditError::Deleted { request, status, field, message },
+			_ => RedditError::ApiError { request, status, code, field, message },
+		})
+	}
+}
+
+// Reads the exact wait reddit sends next to a ratelimit error, in seconds. It is rounded up so a
+// submission isn't resent before the wait is over.
+fn ratelimit_wait(seconds: &Value) -> Option<Duration> {
+	let seconds = seconds.as_f64().filter(|seconds| seconds.is_finite() && *seconds >= 0.0)?;
+	Some(Duration::from_millis((seconds * 1000.0).ceil() as u64))
+}
+
+// Parses the wait out of a ratelimit message such as "you are doing that too much. try again in
+// 5 minutes.", for responses without the exact wait. The wait in the message is rounded down.
+fn parse_ratelimit_wait(message: &str) -> Option<Duration> {
+	let message = message.to_lowercase();
+	let rest = &message[message.find("try again in ")? + "try again in ".len()..];
+	let mut words = rest.split_whitespace();
+	let amount = words.next()?.parse::<u64>().ok()?;
+	let unit = words.next()?;
+
+	if unit.starts_with("millisecond") {
+		Some(Duration::from_millis(amount))
+	} else if unit.starts_with("second") {
+		Some(Duration::from_secs(amount))
+	} else if unit.starts_with("minute") {
+		Some(Duration::from_secs(amount * 60))
+	} else if unit.starts_with("hour") {
+		Some(Duration::from_secs(amount * 60 * 60))
+	} else {
+		None
+	}
+}
+
 /// An error representing a json value that could not be parsed as a certain struct
 #[derive(Debug, Fail)]
 #[fail(display = "Could not parse json {} as {}\n", json, thing_type)]
@@ -58,6 +58,27 @@
 //!
 //! which will return a json value until the actual user data structure is implemented.
 //!
+//! ## Asynchronous usage
+//! Every method of `App` blocks until it's done. If that's not desired, `AsyncApp` has the same
+//! methods, but they return futures instead, which can be run on any executor. `App` is just a thin
+//! wrapper that runs these futures on its own tokio runtime.
+//!
+//! ```rust,no_run
+//! # extern crate futures;
+//! # extern crate orca;
+//! # extern crate tokio;
+//! # use futures::Future;
+//! # use orca::AsyncApp;
+//! # fn main() {
+//! let reddit = AsyncApp::new("a", "b", "c").unwrap();
+//! let mut runtime = tokio::runtime::Runtime::new().unwrap();
+//! let user = runtime.block_on(reddit.get_user("IntrepidPig").map(|user| user["data"]["name"].clone())).unwrap();
+//! # }
+//! ```
+//!
+//! Both `App` and `AsyncApp` are `Send` and `Sync`, so a single authorized instance can be shared
+//! between threads with an `Arc`.
+//!
 
 extern crate chrono;
 #[macro_use]
@@ -70,11 +91,12 @@ extern crate hyper_tls;
 #[macro_use]
 extern crate log;
 extern crate base64;
+extern crate native_tls;
 extern crate open;
 extern crate rand;
 extern crate serde;
 extern crate serde_json as json;
-extern crate tokio_core;
+extern crate tokio;
 extern crate url;
 
 #[cfg(test)]
@@ -92,8 +114,8 @@ pub mod errors;
 /// Main entry point
 pub mod app;
 
-pub use app::App;
+pub use app::{App, AppBuilder, AsyncApp};
 pub use data::{Sort, SortTime};
 pub use errors::RedditError;
-pub use net::auth::{self, InstalledAppError, ResponseGenFn, Scopes};
-pub use net::{Connection, LimitMethod};
+pub use net::auth::{self, AuthorizeUrl, InstalledAppError, ResponseGenFn, Scopes};
+pub use net::{BaseUrls, Connection, ConnectionBuilder, LimitMethod, RedditFuture, RedditStream};
@@ -1,7 +1,7 @@
 //! # Authorization
 //! Authorization for a Reddit client is done by OAuth, which can be done multiple (3) ways. The
-//! possible methods of authorization are Script, Installed App, and Web App. Currently, only
-//! the first two are supported by orca. There are certain use cases for each app type.
+//! possible methods of authorization are Script, Installed App, and Web App, all of which are
+//! supported by orca. There are certain use cases for each app type.
 //!
 //! ## Scripts
 //!
@@ -36,8 +36,8 @@
 //!
 //! Most of this work is implemented for you by orca. At the moment, there is some lacking in
 //! customizability, but that will hopefully change in the future. Currently, orca opens the
-//! reddit.com in the default browser using the `open` crate, and the redirect uri must always be
-//! 127.0.0.1:7878.
+//! reddit.com in the default browser using the `open` crate, logging the url to visit instead if
+//! no browser can be opened, and the redirect uri must always be 127.0.0.1:7878.
 //!
 //! To create an installed app, the process at first is similar to Script app types. Visit
 //! [https://www.reddit.com/prefs/apps](https://www.reddit.com/prefs/apps), and create a new app,
@@ -54,41 +54,86 @@
 //! click allow, the page should redirect to a simple display of the words `Authorization successful`.
 //! Hopefully this too will be customizable one day.
 //!
-//! Installed apps, unlike scripts, require periodic reauthorization, or will expire without the
-//! possibility of refreshing if a permanent duration wasn't requested. This should be done
-//! automatically by the `net::Connection` instance.
+//! On machines without a browser, such as servers reached over SSH or containers, there is also a
+//! manual mode that neither opens a browser nor starts a server. Create an `AuthorizeUrl` and show
+//! its url to the user. After allowing the app, their browser is redirected to the redirect uri,
+//! which fails to load, and they paste the url it tried to load back into the program. Pass that
+//! to `OAuth::create_installed_app_manual`, which checks the state and gets the tokens.
+//!
+//! Installed apps require periodic reauthorization, or will expire without the possibility of
+//! refreshing if a permanent duration wasn't requested. This should be done automatically by the
+//! `net::Connection` instance.
+//!
+//! Script tokens expire too, after an hour. Since a script knows the password of its user, the
+//! connection gets a new token with it shortly before the old one expires, or when reddit rejects
+//! a token, and sends the rejected request again.
+//!
+//! ## Web Apps
+//!
+//! Web apps are used by web services that many users link their reddit accounts to. Like scripts
+//! they can keep a secret, and like installed apps the user authorizes them by visiting a url on
+//! reddit.com, which redirects to the redirect uri of the app afterwards. Unlike installed apps,
+//! the redirect is received by the web service itself rather than by orca.
+//!
+//! Register the app with the web app type and the redirect uri of your service. Create an
+//! `AuthorizeUrl` and send the user to its url, keeping its state, for example in the session of
+//! the user. When reddit redirects the user back, pass the query of the redirect to
+//! `AuthorizeUrl::code_from_query` to check the state and get the code, and pass the code to
+//! `OAuth::create_web_app` to get the tokens. With a permanent authorization, the token is
+//! refreshed automatically like that of an installed app.
+//!
+//! ## Application Only
+//!
+//! Services that only read public data, such as statistics about a subreddit, don't need a user.
+//! They can still authorize as the app itself, with `OAuth::create_client_credentials` for scripts
+//! and web apps or `OAuth::create_installed_client` for installed apps, so their requests get the
+//! higher ratelimit of authorized requests. The token is replaced with a new one automatically
+//! when it expires.
+//!
+//! To avoid asking the user to authorize the app every time the program starts, set a
+//! `TokenStore` such as a `FileTokenStore` on the app with `AppBuilder::token_store`. The
+//! authorization info is saved whenever it changes, and restored and refreshed when the app is
+//! built, so the browser flow is only needed when nothing was saved yet.
 
 use rand::{self, Rng};
 use std;
-use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::fmt;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use base64;
 use failure::Error;
-use futures::future::ok;
+use futures::future::{self, ok};
 use futures::sync::oneshot::{self, Sender};
 use futures::Future;
 use hyper::header::{self, HeaderValue};
 use hyper::server::Server;
 use hyper::service::{MakeService, Service};
-use hyper::{Body, Error as HyperError, Method, Request, Response};
+use hyper::{Body, Error as HyperError, Request, Response};
 use open;
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 use url::{self, Url};
 
 use errors::RedditError;
-use net::body_from_map;
-use net::Connection;
+use net::form::Form;
+use net::{Connection, RedditFuture};
 
 /// Function type that is passed into OAuthApp::InstalledApp to generate response from code retrieval.
 pub type ResponseGenFn = (Fn(&Result<String, InstalledAppError>) -> Response<Body>) + Send + Sync;
 
 type CodeSender = Arc<Mutex<Option<Sender<Result<String, InstalledAppError>>>>>;
 
-/// Enum representing OAuth information that has been aquired from authorization. This should only be
-/// used internally within orca.
+/// How long before a token expires it is refreshed, so it doesn't expire while a request is sent
+pub const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Enum representing OAuth information that has been aquired from authorization. It can be saved
+/// and restored with serde, such as by a `TokenStore`. The saved info contains the secrets of the
+/// app and the tokens of the user, so it should be stored somewhere private. The password of a
+/// script user is never saved, so a restored script can use its token until it expires, but can't
+/// get a new one.
 #[derive(Debug, Clone)]
 pub enum OAuth {
 	/// Script app type
@@ -99,10 +144,12 @@ pub enum OAuth {
 		secret: String,
 		/// Username of the script user
 		username: String,
-		/// Password of the script user
+		/// Password of the script user. Empty if the authorization was restored from saved info.
 		password: String,
 		/// Token retrieved from script authorization
 		token: String,
+		/// Instant when the current token expires
+		expire_instant: Option<Instant>,
 	},
 	/// Installed app type
 	InstalledApp {
@@ -111,54 +158,334 @@ pub enum OAuth {
 		/// Redirect url of the installed app
 		redirect: String,
 		/// Token currently in use
-		token: RefCell<String>,
+		token: String,
+		/// The refresh token (to be used to retrieve a new token once the current one expires).
+		/// Not present if temporary authorization was requested
+		refresh_token: Option<String>,
+		/// Instant when the current token expires
+		expire_instant: Option<Instant>,
+	},
+	/// Web app type
+	WebApp {
+		/// Id of the web app
+		id: String,
+		/// Secret of the web app
+		secret: String,
+		/// Redirect url of the web app
+		redirect: String,
+		/// Token currently in use
+		token: String,
 		/// The refresh token (to be used to retrieve a new token once the current one expires).
 		/// Not present if temporary authorization was requested
-		refresh_token: RefCell<Option<String>>,
+		refresh_token: Option<String>,
+		/// Instant when the current token expires
+		expire_instant: Option<Instant>,
+	},
+	/// Application only authorization of a script or web app, which can keep a secret
+	ClientCredentials {
+		/// Id of the app
+		id: String,
+		/// Secret of the app
+		secret: String,
+		/// Token currently in use
+		token: String,
+		/// Instant when the current token expires
+		expire_instant: Option<Instant>,
+	},
+	/// Application only authorization of an installed app
+	InstalledClient {
+		/// Id of the installed app
+		id: String,
+		/// Id of the device the app is installed on
+		device_id: String,
+		/// Token currently in use
+		token: String,
 		/// Instant when the current token expires
-		expire_instant: Cell<Option<Instant>>,
+		expire_instant: Option<Instant>,
 	},
 }
 
-impl OAuth {
-	/// Refreshes the token (only necessary for installed app types)
-	pub fn refresh(&self, conn: &Connection) -> Result<(), Error> {
+// Field names of saved authorization info
+const FIELDS: &[&str] = &["type", "id", "secret", "username", "redirect", "device_id", "token", "refresh_token", "expires_at"];
+// Types of saved authorization info
+const TYPES: &[&str] = &["script", "installed_app", "web_app", "client_credentials", "installed_client"];
+
+impl Serialize for OAuth {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		// Instants can't be saved, so the expiry is saved as a unix timestamp
+		let mut state;
 		match *self {
-			OAuth::Script { .. } => Ok(()),
+			// The password isn't saved, since it would be kept in plain text
+			OAuth::Script {
+				ref id,
+				ref secret,
+				ref username,
+				ref token,
+				expire_instant,
+				..
+			} => {
+				state = serializer.serialize_struct("OAuth", 6)?;
+				state.serialize_field("type", "script")?;
+				state.serialize_field("id", id)?;
+				state.serialize_field("secret", secret)?;
+				state.serialize_field("username", username)?;
+				state.serialize_field("token", token)?;
+				state.serialize_field("expires_at", &expire_instant.map(to_timestamp))?;
+			}
 			OAuth::InstalledApp {
 				ref id,
-				redirect: ref _redirect,
+				ref redirect,
+				ref token,
+				ref refresh_token,
+				expire_instant,
+			} => {
+				state = serializer.serialize_struct("OAuth", 6)?;
+				state.serialize_field("type", "installed_app")?;
+				state.serialize_field("id", id)?;
+				state.serialize_field("redirect", redirect)?;
+				state.serialize_field("token", token)?;
+				state.serialize_field("refresh_token", refresh_token)?;
+				state.serialize_field("expires_at", &expire_instant.map(to_timestamp))?;
+			}
+			OAuth::WebApp {
+				ref id,
+				ref secret,
+				ref redirect,
 				ref token,
 				ref refresh_token,
-				ref expire_instant,
+				expire_instant,
+			} => {
+				state = serializer.serialize_struct("OAuth", 7)?;
+				state.serialize_field("type", "web_app")?;
+				state.serialize_field("id", id)?;
+				state.serialize_field("secret", secret)?;
+				state.serialize_field("redirect", redirect)?;
+				state.serialize_field("token", token)?;
+				state.serialize_field("refresh_token", refresh_token)?;
+				state.serialize_field("expires_at", &expire_instant.map(to_timestamp))?;
+			}
+			OAuth::ClientCredentials {
+				ref id,
+				ref secret,
+				ref token,
+				expire_instant,
+			} => {
+				state = serializer.serialize_struct("OAuth", 5)?;
+				state.serialize_field("type", "client_credentials")?;
+				state.serialize_field("id", id)?;
+				state.serialize_field("secret", secret)?;
+				state.serialize_field("token", token)?;
+				state.serialize_field("expires_at", &expire_instant.map(to_timestamp))?;
+			}
+			OAuth::InstalledClient {
+				ref id,
+				ref device_id,
+				ref token,
+				expire_instant,
 			} => {
-				let old_refresh_token = if let Some(ref refresh_token) = *refresh_token.borrow() { refresh_token.clone() } else { return Err(RedditError::AuthError.into()) };
-				// Get the access token with the new code we just got
-				let mut params: HashMap<&str, &str> = HashMap::new();
-				params.insert("grant_type", "refresh_token");
-				params.insert("refresh_token", &old_refresh_token);
-
-				// Request for the access token
-				let mut tokenreq = Request::builder().method(Method::POST).uri("https://www.reddit.com/api/v1/access_token/.json").body(body_from_map(&params)).unwrap();
-				// httpS is important
-				tokenreq.headers_mut().insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Basic {}", { base64::encode(&format!("{}:", id)) })).unwrap());
-
-				// Send the request and get the access token as a response
-				let response = conn.run_request(tokenreq)?;
-
-				if let (Some(expires_in), Some(new_token), Some(scope)) = (response.get("expires_in"), response.get("access_token"), response.get("scope")) {
-					let expires_in = expires_in.as_u64().unwrap();
-					let new_token = new_token.as_str().unwrap();
-					let _scope = scope.as_str().unwrap();
-					*token.borrow_mut() = new_token.to_string();
-					expire_instant.set(Some(Instant::now() + Duration::new(expires_in.to_string().parse::<u64>().unwrap(), 0)));
-
-					Ok(())
-				} else {
-					Err(Error::from(RedditError::AuthError))
+				state = serializer.serialize_struct("OAuth", 5)?;
+				state.serialize_field("type", "installed_client")?;
+				state.serialize_field("id", id)?;
+				state.serialize_field("device_id", device_id)?;
+				state.serialize_field("token", token)?;
+				state.serialize_field("expires_at", &expire_instant.map(to_timestamp))?;
+			}
+		}
+		state.end()
+	}
+}
+
+impl<'de> Deserialize<'de> for OAuth {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<OAuth, D::Error> {
+		deserializer.deserialize_struct("OAuth", FIELDS, OAuthVisitor)
+	}
+}
+
+// Reads the fields written by the `Serialize` impl of `OAuth`
+struct OAuthVisitor;
+
+impl<'de> Visitor<'de> for OAuthVisitor {
+	type Value = OAuth;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("saved authorization info")
+	}
+
+	fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<OAuth, A::Error> {
+		let mut fields: HashMap<String, String> = HashMap::new();
+		let mut expires_at = None;
+		while let Some(key) = map.next_key::<String>()? {
+			match key.as_str() {
+				"expires_at" => expires_at = map.next_value::<Option<u64>>()?,
+				name if FIELDS.contains(&name) => {
+					if let Some(value) = map.next_value::<Option<String>>()? {
+						fields.insert(key, value);
+					}
+				}
+				_ => {
+					map.next_value::<IgnoredAny>()?;
 				}
 			}
 		}
+
+		let mut field = |name: &'static str| fields.remove(name).ok_or_else(|| de::Error::missing_field(name));
+		let expire_instant = expires_at.map(from_timestamp);
+		match field("type")?.as_str() {
+			"script" => Ok(OAuth::Script {
+				id: field("id")?,
+				secret: field("secret")?,
+				username: field("username")?,
+				password: String::new(),
+				token: field("token")?,
+				expire_instant,
+			}),
+			"installed_app" => Ok(OAuth::InstalledApp {
+				id: field("id")?,
+				redirect: field("redirect")?,
+				token: field("token")?,
+				refresh_token: field("refresh_token").ok(),
+				expire_instant,
+			}),
+			"web_app" => Ok(OAuth::WebApp {
+				id: field("id")?,
+				secret: field("secret")?,
+				redirect: field("redirect")?,
+				token: field("token")?,
+				refresh_token: field("refresh_token").ok(),
+				expire_instant,
+			}),
+			"client_credentials" => Ok(OAuth::ClientCredentials {
+				id: field("id")?,
+				secret: field("secret")?,
+				token: field("token")?,
+				expire_instant,
+			}),
+			"installed_client" => Ok(OAuth::InstalledClient {
+				id: field("id")?,
+				device_id: field("device_id")?,
+				token: field("token")?,
+				expire_instant,
+			}),
+			other => Err(de::Error::unknown_variant(other, TYPES)),
+		}
+	}
+}
+
+impl OAuth {
+	/// Returns the token currently in use
+	pub fn token(&self) -> &str {
+		match *self {
+			OAuth::Script { ref token, .. } => token,
+			OAuth::InstalledApp { ref token, .. } => token,
+			OAuth::WebApp { ref token, .. } => token,
+			OAuth::ClientCredentials { ref token, .. } => token,
+			OAuth::InstalledClient { ref token, .. } => token,
+		}
+	}
+
+	/// Returns the instant when the token currently in use expires, if it's known
+	pub fn expire_instant(&self) -> Option<Instant> {
+		match *self {
+			OAuth::Script { expire_instant, .. } => expire_instant,
+			OAuth::InstalledApp { expire_instant, .. } => expire_instant,
+			OAuth::WebApp { expire_instant, .. } => expire_instant,
+			OAuth::ClientCredentials { expire_instant, .. } => expire_instant,
+			OAuth::InstalledClient { expire_instant, .. } => expire_instant,
+		}
+	}
+
+	/// Returns whether the token has expired or is about to expire, and should be refreshed
+	/// before it's used
+	pub fn needs_refresh(&self) -> bool {
+		self.expire_instant().map_or(false, |expire_instant| Instant::now() + EXPIRY_MARGIN >= expire_instant)
+	}
+
+	/// Returns whether `refresh` can get a new token once the current one expires
+	pub fn can_refresh(&self) -> bool {
+		match *self {
+			OAuth::InstalledApp { ref refresh_token, .. } => refresh_token.is_some(),
+			OAuth::WebApp { ref refresh_token, .. } => refresh_token.is_some(),
+			// Scripts and application only authorization get a new token with the grant they got the
+			// first one with, which a restored script can't since its password isn't saved
+			OAuth::Script { ref password, .. } => !password.is_empty(),
+			OAuth::ClientCredentials { .. } | OAuth::InstalledClient { .. } => true,
+		}
+	}
+
+	/// Returns whether the token acts on behalf of a user, rather than only the app
+	pub fn is_user(&self) -> bool {
+		match *self {
+			OAuth::Script { .. } | OAuth::InstalledApp { .. } | OAuth::WebApp { .. } => true,
+			OAuth::ClientCredentials { .. } | OAuth::InstalledClient { .. } => false,
+		}
+	}
+
+	/// Refreshes the token. The future resolves to the authorization info with the new token, which
+	/// replaces this one. If reddit rotates the refresh token, the new refresh token replaces the old
+	/// one too. Scripts and application only authorization have no refresh token, and are refreshed
+	/// by requesting a new token the same way as the first one.
+	pub fn refresh(&self, conn: &Connection) -> RedditFuture<OAuth> {
+		let (id, secret, refresh_token) = match *self {
+			OAuth::Script {
+				ref id,
+				ref secret,
+				ref username,
+				ref password,
+				..
+			} if !password.is_empty() => return OAuth::create_script(conn, id, secret, username, password),
+			OAuth::Script { .. } => return Box::new(future::err(RedditError::AuthError.into())),
+			OAuth::ClientCredentials { ref id, ref secret, .. } => return OAuth::create_client_credentials(conn, id, secret),
+			OAuth::InstalledClient { ref id, ref device_id, .. } => return OAuth::create_installed_client(conn, id, device_id),
+			OAuth::InstalledApp { ref id, ref refresh_token, .. } => (id, "", refresh_token),
+			OAuth::WebApp {
+				ref id,
+				ref secret,
+				ref refresh_token,
+				..
+			} => (id, secret.as_str(), refresh_token),
+		};
+		let old_refresh_token = if let Some(ref refresh_token) = *refresh_token { refresh_token.clone() } else { return Box::new(future::err(RedditError::AuthError.into())) };
+
+		// Get the access token with the refresh token
+		let form = Form::new().param("grant_type", "refresh_token").param("refresh_token", &old_refresh_token);
+		let mut tokenreq = form.post(&conn.token_url("/api/v1/access_token/.json"));
+		// httpS is important
+		let authorization = match basic_auth(id, secret) {
+			Ok(authorization) => authorization,
+			Err(e) => return Box::new(future::err(e)),
+		};
+		tokenreq.headers_mut().insert(header::AUTHORIZATION, authorization);
+
+		let mut auth = self.clone();
+
+		// Send the request and get the access token as a response
+		Box::new(conn.run_request(tokenreq).and_then(move |response| {
+			if let (Some(expires_in), Some(new_token)) = (response.get("expires_in").and_then(|expires_in| expires_in.as_u64()), response.get("access_token").and_then(|token| token.as_str())) {
+				let new_refresh_token = response.get("refresh_token").and_then(|token| token.as_str()).map(|token| token.to_string()).unwrap_or(old_refresh_token);
+				match auth {
+					OAuth::InstalledApp {
+						ref mut token,
+						ref mut refresh_token,
+						ref mut expire_instant,
+						..
+					}
+					| OAuth::WebApp {
+						ref mut token,
+						ref mut refresh_token,
+						ref mut expire_instant,
+						..
+					} => {
+						*token = new_token.to_string();
+						*refresh_token = Some(new_refresh_token);
+						*expire_instant = Some(Instant::now() + Duration::new(expires_in, 0));
+					}
+					_ => unreachable!(),
+				}
+				Ok(auth)
+			} else {
+				Err(Error::from(RedditError::AuthError))
+			}
+		}))
 	}
 
 	/// Authorize the app as a script
@@ -168,36 +495,121 @@ impl OAuth {
 	/// * `secret` - The app secret registered on Reddit
 	/// * `username` - The username of the user to authorize as
 	/// * `password` - The password of the user to authorize as
-	pub fn create_script(conn: &Connection, id: &str, secret: &str, username: &str, password: &str) -> Result<OAuth, Error> {
+	pub fn create_script(conn: &Connection, id: &str, secret: &str, username: &str, password: &str) -> RedditFuture<OAuth> {
 		// authorization paramaters to request
-		let mut params: HashMap<&str, &str> = HashMap::new();
-		params.insert("grant_type", "password");
-		params.insert("username", &username);
-		params.insert("password", &password);
+		let form = Form::new().param("grant_type", "password").param("username", username).param("password", password);
 
 		// Request for the bearer token
-		let mut tokenreq = Request::builder().method(Method::POST).uri("https://ssl.reddit.com/api/v1/access_token/.json").body(body_from_map(&params)).unwrap();
+		let mut tokenreq = form.post(&conn.token_url("/api/v1/access_token/.json"));
 		// httpS is important
-		tokenreq.headers_mut().insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Basic {}", { base64::encode(&format!("{}:{}", id, secret)) })).unwrap());
+		let authorization = match basic_auth(id, secret) {
+			Ok(authorization) => authorization,
+			Err(e) => return Box::new(future::err(e)),
+		};
+		tokenreq.headers_mut().insert(header::AUTHORIZATION, authorization);
+
+		let (id, secret, username, password) = (id.to_string(), secret.to_string(), username.to_string(), password.to_string());
 
 		// Send the request and get the bearer token as a response
-		let response = conn.run_request(tokenreq)?;
-
-		if let Some(token) = response.get("access_token") {
-			let token = token.as_str().unwrap().to_string();
-			Ok(OAuth::Script {
-				id: id.to_string(),
-				secret: secret.to_string(),
-				username: username.to_string(),
-				password: password.to_string(),
-				token,
-			})
-		} else {
-			Err(RedditError::AuthError.into())
-		}
+		Box::new(conn.run_request(tokenreq).and_then(move |response| {
+			if let Some(token) = response.get("access_token").and_then(|token| token.as_str()) {
+				Ok(OAuth::Script {
+					id,
+					secret,
+					username,
+					password,
+					token: token.to_string(),
+					expire_instant: response.get("expires_in").and_then(|expires_in| expires_in.as_u64()).map(|expires_in| Instant::now() + Duration::new(expires_in, 0)),
+				})
+			} else {
+				Err(RedditError::AuthError.into())
+			}
+		}))
+	}
+
+	/// Authorize the app as a web app, with a code reddit redirected the user to the redirect uri
+	/// with. Get the url to send the user to with `AuthorizeUrl::new`, and the code from the query
+	/// of the redirect with `AuthorizeUrl::code_from_query`. orca doesn't receive the redirect
+	/// itself, so it can be handled by the web service the app belongs to.
+	/// # Arguments
+	/// * `conn` - A refernce to the connection to authorize
+	/// * `id` - The app id registered on Reddit
+	/// * `secret` - The app secret registered on Reddit
+	/// * `redirect` - The app redirect URI registered on Reddit
+	/// * `code` - The code reddit redirected the user with
+	pub fn create_web_app(conn: &Connection, id: &str, secret: &str, redirect: &str, code: &str) -> RedditFuture<OAuth> {
+		let form = Form::new().param("grant_type", "authorization_code").param("code", code).param("redirect_uri", redirect);
+
+		// Request for the access token
+		let mut tokenreq = form.post(&conn.token_url("/api/v1/access_token/.json"));
+		// httpS is important
+		let authorization = match basic_auth(id, secret) {
+			Ok(authorization) => authorization,
+			Err(e) => return Box::new(future::err(e)),
+		};
+		tokenreq.headers_mut().insert(header::AUTHORIZATION, authorization);
+
+		let (id, secret, redirect) = (id.to_string(), secret.to_string(), redirect.to_string());
+
+		// Send the request and get the access token as a response
+		Box::new(conn.run_request(tokenreq).and_then(move |response| {
+			if let (Some(expires_in), Some(token)) = (response.get("expires_in").and_then(|expires_in| expires_in.as_u64()), response.get("access_token").and_then(|token| token.as_str())) {
+				Ok(OAuth::WebApp {
+					id,
+					secret,
+					redirect,
+					token: token.to_string(),
+					// Not present if temporary authorization was requested
+					refresh_token: response.get("refresh_token").and_then(|token| token.as_str()).map(|token| token.to_string()),
+					expire_instant: Some(Instant::now() + Duration::new(expires_in, 0)),
+				})
+			} else {
+				Err(Error::from(RedditError::AuthError))
+			}
+		}))
+	}
+
+	/// Authorize a script or web app without a user, with the `client_credentials` grant. Requests
+	/// are sent to the OAuth host with the token, which gets a higher ratelimit than requests
+	/// without authorization, but can only read public data.
+	/// # Arguments
+	/// * `conn` - A reference to the connection to authorize
+	/// * `id` - The app id registered on Reddit
+	/// * `secret` - The app secret registered on Reddit
+	pub fn create_client_credentials(conn: &Connection, id: &str, secret: &str) -> RedditFuture<OAuth> {
+		let form = Form::new().param("grant_type", "client_credentials");
+		let (id, secret) = (id.to_string(), secret.to_string());
+
+		Box::new(app_only_token(conn, form, &id, &secret).map(move |(token, expire_instant)| OAuth::ClientCredentials {
+			id,
+			secret,
+			token,
+			expire_instant: Some(expire_instant),
+		}))
+	}
+
+	/// Authorize an installed app without a user, with the `installed_client` grant. Requests are
+	/// sent like those of `create_client_credentials`.
+	/// # Arguments
+	/// * `conn` - A reference to the connection to authorize
+	/// * `id` - The app id registered on Reddit
+	/// * `device_id` - A unique id of 20 to 30 characters for the device the app is installed on,
+	/// which should be the same every time the app runs on it, or `DO_NOT_TRACK_THIS_DEVICE`
+	pub fn create_installed_client(conn: &Connection, id: &str, device_id: &str) -> RedditFuture<OAuth> {
+		let form = Form::new().param("grant_type", "https://oauth.reddit.com/grants/installed_client").param("device_id", device_id);
+		let (id, device_id) = (id.to_string(), device_id.to_string());
+
+		Box::new(app_only_token(conn, form, &id, "").map(move |(token, expire_instant)| OAuth::InstalledClient {
+			id,
+			device_id,
+			token,
+			expire_instant: Some(expire_instant),
+		}))
 	}
 
-	/// Authorize the app as an installed app
+	/// Authorize the app as an installed app. The future starts a server on the redirect uri
+	/// and resolves once the user has authorized the app in their browser and the code has been
+	/// exchanged for a token.
 	/// # Arguments
 	/// * `conn` - A reference to the connection to authorize
 	/// * `id` - The app id registered on Reddit
@@ -209,32 +621,29 @@ impl OAuth {
 	/// that an error occurred within the function.
 	/// * `scopes` - A reference to a Scopes instance representing the capabilites you are requesting
 	/// as an installed app.
-	pub fn create_installed_app<I: Into<Option<Arc<ResponseGenFn>>>>(conn: &Connection, id: &str, redirect: &str, response_gen: I, scopes: &Scopes) -> Result<OAuth, Error> {
+	pub fn create_installed_app<I: Into<Option<Arc<ResponseGenFn>>>>(conn: &Connection, id: &str, redirect: &str, response_gen: I, scopes: &Scopes) -> RedditFuture<OAuth> {
 		let response_gen = response_gen.into();
-		// Random state string to identify this authorization instance
-		let state = rand::thread_rng().gen_ascii_chars().take(16).collect::<String>();
-
-		let scopes = &scopes.to_string();
-		let browser_uri = format!(
-			"https://www.reddit.com/api/v1/authorize?client_id={}&response_type=code&\
-			 state={}&redirect_uri={}&duration=permanent&scope={}",
-			id, state, redirect, scopes
-		);
+		let AuthorizeUrl { url: browser_uri, state } = match AuthorizeUrl::new(conn, id, redirect, scopes, true) {
+			Ok(authorize) => authorize,
+			Err(e) => return Box::new(future::err(e)),
+		};
 
 		let state_rc = Arc::new(state);
 
-		// Open the auth url in the browser so the user can authenticate the app
-		thread::spawn(move || {
-			open::that(browser_uri).expect("Failed to open browser");
-		});
-
 		// A oneshot future channel that the hyper server has access to to send the code back
 		// to this thread.
 		let (code_sender, code_reciever) = oneshot::channel::<Result<String, InstalledAppError>>();
 
 		// Convert the redirect url into something parseable by the HTTP server
-		let redirect_url = Url::parse(&redirect)?;
+		let redirect_url = match Url::parse(&redirect) {
+			Ok(url) => url,
+			Err(e) => return Box::new(future::err(e.into())),
+		};
 		let main_redirect = format!("{}:{}", redirect_url.host_str().unwrap_or("127.0.0.1"), redirect_url.port().unwrap_or(7878).to_string());
+		let main_redirect = match main_redirect.as_str().parse() {
+			Ok(addr) => addr,
+			Err(e) => return Box::new(future::err(Error::from(e))),
+		};
 
 		// Set the default response generator if necessary
 		let response_gen = if let Some(ref response_gen) = response_gen {
@@ -250,10 +659,23 @@ impl OAuth {
 
 		// Create a server with the instance of a NewInstalledAppService struct with the
 		// responses given, the oneshot sender and the generated state string
-		let server = Server::bind(&main_redirect.as_str().parse()?).serve(MakeInstalledAppService {
-			code_sender: Arc::new(Mutex::new(Some(code_sender))),
-			state: Arc::clone(&state_rc),
-			response_gen: Arc::clone(&response_gen),
+		let server = match Server::try_bind(&main_redirect) {
+			Ok(server) => server.serve(MakeInstalledAppService {
+				code_sender: Arc::new(Mutex::new(Some(code_sender))),
+				state: Arc::clone(&state_rc),
+				response_gen: Arc::clone(&response_gen),
+			}),
+			Err(e) => return Box::new(future::err(e.into())),
+		};
+
+		// Open the auth url in the browser so the user can authenticate the app. Without a browser
+		// the user can still visit the url by hand.
+		thread::spawn(move || {
+			match open::that(&browser_uri) {
+				Ok(ref status) if status.success() => {}
+				Ok(status) => warn!("Failed to open a browser ({}). Visit {} to authorize the app", status, browser_uri),
+				Err(e) => warn!("Failed to open a browser ({}). Visit {} to authorize the app", e, browser_uri),
+			}
 		});
 
 		// Create a code value that is optional but should be set eventually
@@ -281,44 +703,175 @@ impl OAuth {
 
 		let graceful = server.with_graceful_shutdown(finish).map_err(|e| eprintln!("Server failed: {}", e));
 
+		let conn = conn.clone();
+		let id = id.to_string();
+		let redirect = redirect.to_string();
+
 		// Run the server until the code future oneshot resolves and has set the code variable.
-		hyper::rt::run(graceful);
+		Box::new(graceful.then(move |_| {
+			// Make sure we got the code. Return an error if we didn't.
+			let code = match *code.lock().unwrap() {
+				Ok(ref new_code) => new_code.clone(),
+				Err(ref e) => return Box::new(future::err(e.clone().into())) as RedditFuture<OAuth>,
+			};
 
-		// Make sure we got the code. Return an error if we didn't.
-		let code = match *code.lock().unwrap() {
-			Ok(ref new_code) => new_code.clone(),
-			Err(ref e) => return Err(e.clone().into()),
-		};
+			OAuth::create_installed_app_from_code(&conn, &id, &redirect, &code)
+		}))
+	}
 
-		// Get the access token with the new code we just got
-		let mut params: HashMap<&str, &str> = HashMap::new();
-		params.insert("grant_type", "authorization_code");
-		params.insert("code", &code);
-		params.insert("redirect_uri", &redirect);
+	/// Authorize the app as an installed app without opening a browser or starting a server, for
+	/// machines without a desktop such as servers reached over SSH or containers. Show the url of
+	/// an `AuthorizeUrl` to the user, and ask them to paste the url reddit redirected their browser
+	/// to once they authorized the app. The page at that url doesn't have to load.
+	/// # Arguments
+	/// * `conn` - A reference to the connection to authorize
+	/// * `id` - The app id registered on Reddit
+	/// * `redirect` - The app redirect URI registered on Reddit
+	/// * `authorize` - The url the user was sent to, made with `AuthorizeUrl::new`
+	/// * `pasted` - The url the user was redirected to, or its query, as pasted by the user
+	pub fn create_installed_app_manual(conn: &Connection, id: &str, redirect: &str, authorize: &AuthorizeUrl, pasted: &str) -> RedditFuture<OAuth> {
+		match authorize.code_from_redirect(pasted) {
+			Ok(code) => OAuth::create_installed_app_from_code(conn, id, redirect, &code),
+			Err(e) => Box::new(future::err(e.into())),
+		}
+	}
+
+	/// Authorize the app as an installed app with a code reddit redirected the user with, which
+	/// was received some other way than by `create_installed_app`
+	/// # Arguments
+	/// * `conn` - A reference to the connection to authorize
+	/// * `id` - The app id registered on Reddit
+	/// * `redirect` - The app redirect URI registered on Reddit
+	/// * `code` - The code reddit redirected the user with
+	pub fn create_installed_app_from_code(conn: &Connection, id: &str, redirect: &str, code: &str) -> RedditFuture<OAuth> {
+		// Get the access token with the code
+		let form = Form::new().param("grant_type", "authorization_code").param("code", code).param("redirect_uri", redirect);
 
 		// Request for the access token
-		let mut tokenreq = Request::builder().method(Method::POST).uri("https://ssl.reddit.com/api/v1/access_token/.json").body(body_from_map(&params)).unwrap();
+		let mut tokenreq = form.post(&conn.token_url("/api/v1/access_token/.json"));
 		// httpS is important
-		tokenreq.headers_mut().insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Basic {}", base64::encode(&format!("{}:", id)))).unwrap());
+		let authorization = match basic_auth(id, "") {
+			Ok(authorization) => authorization,
+			Err(e) => return Box::new(future::err(e)),
+		};
+		tokenreq.headers_mut().insert(header::AUTHORIZATION, authorization);
+
+		let (id, redirect) = (id.to_string(), redirect.to_string());
 
 		// Send the request and get the access token as a response
-		let response = conn.run_request(tokenreq)?;
-
-		if let (Some(expires_in), Some(token), Some(refresh_token), Some(scope)) = (response.get("expires_in"), response.get("access_token"), response.get("refresh_token"), response.get("scope")) {
-			let expires_in = expires_in.as_u64().unwrap();
-			let token = token.as_str().unwrap();
-			let refresh_token = refresh_token.as_str().unwrap();
-			let _scope = scope.as_str().unwrap();
-			Ok(OAuth::InstalledApp {
-				id: id.to_string(),
-				redirect: redirect.to_string(),
-				token: RefCell::new(token.to_string()),
-				refresh_token: RefCell::new(Some(refresh_token.to_string())),
-				expire_instant: Cell::new(Some(Instant::now() + Duration::new(expires_in.to_string().parse::<u64>().unwrap(), 0))),
-			})
+		Box::new(conn.run_request(tokenreq).and_then(move |response| {
+			if let (Some(expires_in), Some(token)) = (response.get("expires_in").and_then(|expires_in| expires_in.as_u64()), response.get("access_token").and_then(|token| token.as_str())) {
+				Ok(OAuth::InstalledApp {
+					id,
+					redirect,
+					token: token.to_string(),
+					// Not present if temporary authorization was requested
+					refresh_token: response.get("refresh_token").and_then(|token| token.as_str()).map(|token| token.to_string()),
+					expire_instant: Some(Instant::now() + Duration::new(expires_in, 0)),
+				})
+			} else {
+				Err(Error::from(RedditError::AuthError))
+			}
+		}))
+	}
+}
+
+/// A url to send a user to for authorizing an installed or web app, and the random state reddit
+/// redirects back with. The state has to be kept until the redirect arrives, such as in the
+/// session of the user, to check that the redirect belongs to this authorization.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorizeUrl {
+	/// The url to send the user to
+	pub url: String,
+	/// The state identifying this authorization
+	pub state: String,
+}
+
+impl AuthorizeUrl {
+	/// Creates the url with a new random state
+	/// # Arguments
+	/// * `conn` - A reference to the connection whose base url to use
+	/// * `id` - The app id registered on Reddit
+	/// * `redirect` - The app redirect URI registered on Reddit
+	/// * `scopes` - A reference to a Scopes instance representing the capabilites you are requesting
+	/// * `permanent` - Whether to request a refresh token, so the app stays authorized after the
+	/// first token expires
+	pub fn new(conn: &Connection, id: &str, redirect: &str, scopes: &Scopes, permanent: bool) -> Result<AuthorizeUrl, Error> {
+		// Random state string to identify this authorization instance
+		let state = rand::thread_rng().gen_ascii_chars().take(16).collect::<String>();
+		let duration = if permanent { "permanent" } else { "temporary" };
+		let url = Url::parse_with_params(
+			&conn.public_url("/api/v1/authorize"),
+			&[("client_id", id), ("response_type", "code"), ("state", &state), ("redirect_uri", redirect), ("duration", duration), ("scope", &scopes.to_string())],
+		)?;
+
+		Ok(AuthorizeUrl { url: url.into_string(), state })
+	}
+
+	/// Gets the code from the query of the redirect to the redirect uri, checking that its state
+	/// matches. Fails if the user denied the authorization.
+	/// # Arguments
+	/// * `query` - The query string of the redirect, with or without the leading `?`
+	pub fn code_from_query(&self, query: &str) -> Result<String, InstalledAppError> {
+		code_from_query(query, &self.state)
+	}
+
+	/// Gets the code from a redirect pasted by the user, which can be the whole url reddit
+	/// redirected to or its query. A code pasted on its own is rejected, since the state has to be
+	/// checked to be sure the code belongs to this authorization.
+	/// # Arguments
+	/// * `pasted` - The text the user pasted
+	pub fn code_from_redirect(&self, pasted: &str) -> Result<String, InstalledAppError> {
+		// Reddit sometimes ends the redirect with a fragment like `#_`
+		let pasted = pasted.trim().split('#').next().unwrap_or("");
+
+		if pasted.is_empty() {
+			Err(InstalledAppError::NeverRecieved)
+		} else if let Some(start) = pasted.find('?') {
+			self.code_from_query(&pasted[start + 1..])
+		} else {
+			// Without a state, this fails with a missing state
+			self.code_from_query(pasted)
+		}
+	}
+}
+
+// Requests an application only token, resolving to the token and when it expires
+fn app_only_token(conn: &Connection, form: Form, id: &str, secret: &str) -> RedditFuture<(String, Instant)> {
+	let mut tokenreq = form.post(&conn.token_url("/api/v1/access_token/.json"));
+	// httpS is important
+	let authorization = match basic_auth(id, secret) {
+		Ok(authorization) => authorization,
+		Err(e) => return Box::new(future::err(e)),
+	};
+	tokenreq.headers_mut().insert(header::AUTHORIZATION, authorization);
+
+	Box::new(conn.run_request(tokenreq).and_then(|response| {
+		if let (Some(expires_in), Some(token)) = (response.get("expires_in").and_then(|expires_in| expires_in.as_u64()), response.get("access_token").and_then(|token| token.as_str())) {
+			Ok((token.to_string(), Instant::now() + Duration::new(expires_in, 0)))
 		} else {
 			Err(Error::from(RedditError::AuthError))
 		}
+	}))
+}
+
+// Creates the basic authorization header for a token request of an app
+fn basic_auth(id: &str, secret: &str) -> Result<HeaderValue, Error> {
+	HeaderValue::from_str(&format!("Basic {}", base64::encode(&format!("{}:{}", id, secret)))).map_err(|_| RedditError::AuthError.into())
+}
+
+// Converts an instant to a unix timestamp, as instants can't be saved
+fn to_timestamp(instant: Instant) -> u64 {
+	let now = Instant::now();
+	let time = if instant > now { SystemTime::now() + (instant - now) } else { SystemTime::now() - (now - instant) };
+	time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+// Converts a unix timestamp made by `to_timestamp` back to an instant
+fn from_timestamp(timestamp: u64) -> Instant {
+	match (UNIX_EPOCH + Duration::from_secs(timestamp)).duration_since(SystemTime::now()) {
+		Ok(remaining) => Instant::now() + remaining,
+		Err(e) => Instant::now() - e.duration(),
 	}
 }
 
@@ -499,9 +1052,12 @@ pub enum InstalledAppError {
 		/// The message included in the error
 		msg: String,
 	},
-	/// The state string wasn't present or did not match
+	/// The state string did not match
 	#[fail(display = "The states did not match")]
 	MismatchedState,
+	/// The redirect had no state string, such as when only the code was pasted
+	#[fail(display = "The redirect has no state. Paste the whole url that was redirected to, or use create_installed_app_from_code for a code on its own")]
+	MissingState,
 	/// The code has already been recieved
 	#[fail(display = "A code was already recieved")]
 	AlreadyRecieved,
@@ -549,11 +1105,6 @@ impl Service for InstalledAppService {
 	type Future = Box<Future<Item = Response<Self::ResBody>, Error = Self::Error> + Send>;
 
 	fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
-		// Get the data from the request (the state and the code, or the error) in a HashMap
-		let query_str = req.uri().path_and_query().unwrap().as_str();
-		let query_str = &query_str[2..query_str.len()];
-		let params: HashMap<_, _> = url::form_urlencoded::parse(query_str.as_bytes()).collect();
-
 		// Create a HTTP response based on the result of the code retrieval, the code sender, and the
 		// response generator.
 		fn create_res(gen: &ResponseGenFn, res: &Result<String, InstalledAppError>, sender: &CodeSender) -> <InstalledAppService as Service>::Future {
@@ -570,44 +1121,29 @@ impl Service for InstalledAppService {
 			Box::new(ok(resp))
 		}
 
-		// If there was an error stop here, returning the error response
-		if params.contains_key("error") {
-			warn!("Got failed authorization. Error was {}", &params["error"]);
-			let err = InstalledAppError::Error { msg: params["error"].to_string() };
-			create_res(&*self.response_gen, &Err(err.clone()), &self.code_sender)
-		} else {
-			// Get the state if it exists
-			let state = if let Some(state) = params.get("state") {
-				state
-			} else {
-				// Return error response if we didn't get the state
-				return create_res(&*self.response_gen, &Err(InstalledAppError::MismatchedState), &self.code_sender);
-			};
-			// Error if the state doesn't match
-			if *state != *self.state {
-				error!("State didn't match. Got state \"{}\", needed state \"{}\"", state, self.state);
-				create_res(&*self.response_gen, &Err(InstalledAppError::MismatchedState), &self.code_sender)
-			} else {
-				// Get the code and send it with the oneshot sender back to the main thread
-				let code = &params["code"];
-				create_res(&*self.response_gen, &Ok(code.clone().into()), &self.code_sender)
-			}
-		}
+		// Get the code from the query, or the error, and send it back to the main thread
+		let res = code_from_query(req.uri().query().unwrap_or(""), &self.state);
+		create_res(&*self.response_gen, &res, &self.code_sender)
 	}
 }
 
-// A neat trait I came up with. If you have a RefCell<Option<T>>, then you can call pop() on it and
-// it will take the value out of the RefCell and give it back. If it doesn't exist, then it just returns None.
-trait RefCellExt<T> {
-	fn pop(&self) -> Option<T>;
-}
+// Gets the code from the query of a redirect to the redirect uri, checking that the state matches
+fn code_from_query(query: &str, state: &str) -> Result<String, InstalledAppError> {
+	let params: HashMap<_, _> = url::form_urlencoded::parse(query.trim_start_matches('?').as_bytes()).collect();
 
-impl<T: std::fmt::Debug> RefCellExt<T> for RefCell<Option<T>> {
-	fn pop(&self) -> Option<T> {
-		if self.borrow().is_some() {
-			return std::mem::replace(&mut *self.borrow_mut(), None);
+	// If there was an error stop here
+	if let Some(error) = params.get("error") {
+		warn!("Got failed authorization. Error was {}", error);
+		return Err(InstalledAppError::Error { msg: error.to_string() });
+	}
+	match params.get("state") {
+		Some(got) if &**got == state => {}
+		Some(got) => {
+			error!("State didn't match. Got state \"{}\", needed state \"{}\"", got, state);
+			return Err(InstalledAppError::MismatchedState);
 		}
-
-		None
+		None => return Err(InstalledAppError::MissingState),
 	}
+
+	params.get("code").map(|code| code.to_string()).ok_or_else(|| InstalledAppError::Error { msg: "The redirect has no code".to_string() })
 }
@@ -0,0 +1,273 @@
+//! # Response cache
+//! A `ResponseCache` keeps the responses to GET requests, so endpoints that are polled repeatedly,
+//! such as user and subreddit about pages, don't use up the ratelimit budget. A cache is added to a
+//! connection with `ConnectionBuilder::cache`.
+//!
+//! Only endpoints with a time to live are cached. Until it runs out, a cached response is returned
+//! without sending a request at all. After that, if reddit sent an `ETag` or `Last-Modified` header
+//! with the response, the request is sent with `If-None-Match` or `If-Modified-Since`, and a 304 Not
+//! Modified answer is a cache hit that keeps the cached response for another time to live.
+//! `Cache-Control` headers are ignored, since reddit marks most responses as not cacheable.
+//!
+//! Responses are stored in a `CacheStore`, which is an in-memory `LruCache` by default. Stores are
+//! keyed by url only, so requests sent on behalf of a user are never cached, since their responses
+//! can differ between users. Unauthorized and application only requests are.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use failure::Error;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::Uri;
+use json::{self, Value};
+
+/// A response as kept in a cache
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedResponse {
+	/// Body of the response
+	pub body: String,
+	/// The `ETag` header of the response, if it had one
+	pub etag: Option<String>,
+	/// The `Last-Modified` header of the response, if it had one
+	pub last_modified: Option<String>,
+	/// When the response has to be revalidated
+	pub expires: SystemTime,
+}
+
+impl CachedResponse {
+	/// Whether the response can still be used without revalidating it
+	pub fn is_fresh(&self) -> bool {
+		SystemTime::now() < self.expires
+	}
+
+	/// Whether the response can be revalidated with a conditional request
+	pub fn is_revalidatable(&self) -> bool {
+		self.etag.is_some() || self.last_modified.is_some()
+	}
+
+	/// Converts the response to json, for stores that keep responses outside of memory
+	pub fn to_value(&self) -> Value {
+		let expires = self.expires.duration_since(UNIX_EPOCH).unwrap_or_default();
+		json::json!({
+			"body": self.body,
+			"etag": self.etag,
+			"last_modified": self.last_modified,
+			"expires": expires.as_secs() * 1000 + u64::from(expires.subsec_nanos() / 1_000_000),
+		})
+	}
+
+	/// Reads a response from the json made by `to_value`
+	pub fn from_value(value: &Value) -> Result<CachedResponse, Error> {
+		Ok(CachedResponse {
+			body: value["body"].as_str().ok_or_else(|| format_err!("Cached response is missing its body"))?.to_string(),
+			etag: value["etag"].as_str().map(|etag| etag.to_string()),
+			last_modified: value["last_modified"].as_str().map(|date| date.to_string()),
+			expires: UNIX_EPOCH + Duration::from_millis(value["expires"].as_u64().ok_or_else(|| format_err!("Cached response is missing its expiry"))?),
+		})
+	}
+}
+
+/// Somewhere to keep cached responses. A store can be shared between connections, which is why it
+/// has to keep its state behind a lock.
+pub trait CacheStore: Send + Sync {
+	/// Returns the response stored for a key, if any
+	fn get(&self, key: &str) -> Option<CachedResponse>;
+
+	/// Stores a response, replacing the one stored for the key before
+	fn put(&self, key: &str, response: CachedResponse);
+
+	/// Removes the response stored for a key
+	fn remove(&self, key: &str);
+}
+
+impl<S: CacheStore + ?Sized> CacheStore for Arc<S> {
+	fn get(&self, key: &str) -> Option<CachedResponse> {
+		(**self).get(key)
+	}
+
+	fn put(&self, key: &str, response: CachedResponse) {
+		(**self).put(key, response)
+	}
+
+	fn remove(&self, key: &str) {
+		(**self).remove(key)
+	}
+}
+
+impl<S: CacheStore + ?Sized> CacheStore for Box<S> {
+	fn get(&self, key: &str) -> Option<CachedResponse> {
+		(**self).get(key)
+	}
+
+	fn put(&self, key: &str, response: CachedResponse) {
+		(**self).put(key, response)
+	}
+
+	fn remove(&self, key: &str) {
+		(**self).remove(key)
+	}
+}
+
+/// An in-memory store that holds a limited amount of responses, dropping the least recently used
+/// one when it is full
+#[derive(Debug)]
+pub struct LruCache {
+	capacity: usize,
+	entries: Mutex<(HashMap<String, CachedResponse>, VecDeque<String>)>,
+}
+
+impl LruCache {
+	/// Creates an empty store
+	/// # Arguments
+	/// * `capacity` - The most responses to hold at once
+	pub fn new(capacity: usize) -> LruCache {
+		LruCache { capacity, entries: Mutex::new((HashMap::new(), VecDeque::new())) }
+	}
+
+	/// Returns the amount of responses held
+	pub fn len(&self) -> usize {
+		self.entries.lock().unwrap().0.len()
+	}
+
+	/// Whether no responses are held
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+// Moves a key to the back of the usage order, as the most recently used one
+fn touch(order: &mut VecDeque<String>, key: &str) {
+	if let Some(i) = order.iter().position(|k| k == key) {
+		order.remove(i);
+	}
+	order.push_back(key.to_string());
+}
+
+impl CacheStore for LruCache {
+	fn get(&self, key: &str) -> Option<CachedResponse> {
+		let mut entries = self.entries.lock().unwrap();
+		let (ref map, ref mut order) = *entries;
+		let response = map.get(key).cloned();
+		if response.is_some() {
+			touch(order, key);
+		}
+		response
+	}
+
+	fn put(&self, key: &str, response: CachedResponse) {
+		if self.capacity == 0 {
+			return;
+		}
+		let mut entries = self.entries.lock().unwrap();
+		let (ref mut map, ref mut order) = *entries;
+		map.insert(key.to_string(), response);
+		touch(order, key);
+		while map.len() > self.capacity {
+			match order.pop_front() {
+				Some(oldest) => map.remove(&oldest),
+				None => break,
+			};
+		}
+	}
+
+	fn remove(&self, key: &str) {
+		let mut entries = self.entries.lock().unwrap();
+		let (ref mut map, ref mut order) = *entries;
+		map.remove(key);
+		if let Some(i) = order.iter().position(|k| k == key) {
+			order.remove(i);
+		}
+	}
+}
+
+/// Which GET responses a connection caches, for how long, and where they are kept
+pub struct ResponseCache {
+	store: Box<CacheStore>,
+	ttls: Vec<(String, Duration)>,
+	default_ttl: Option<Duration>,
+}
+
+impl ResponseCache {
+	/// Creates a cache keeping up to 256 responses in memory. No endpoint is cached until it is
+	/// given a time to live.
+	pub fn new() -> ResponseCache {
+		ResponseCache::with_store(LruCache::new(256))
+	}
+
+	/// Creates a cache keeping responses in a store. To share a store between connections, pass
+	/// the same `Arc` to each of them.
+	pub fn with_store<S: CacheStore + 'static>(store: S) -> ResponseCache {
+		ResponseCache { store: Box::new(store), ttls: Vec::new(), default_ttl: None }
+	}
+
+	/// Sets how long responses from matching endpoints are used before they are revalidated. A
+	/// path matches if the path of the request starts with its segments, where a `*` segment
+	/// matches any single segment, such as `/r/*/about` for subreddit about pages. When several
+	/// paths match, the one added first is used. A time to live of zero revalidates every time.
+	/// # Arguments
+	/// * `path` - The path of the endpoints
+	/// * `ttl` - How long responses are used without revalidating them
+	pub fn ttl(mut self, path: &str, ttl: Duration) -> ResponseCache {
+		self.ttls.push((path.to_string(), ttl));
+		self
+	}
+
+	/// Sets the time to live for endpoints that don't match any path given to `ttl`. By default
+	/// they aren't cached.
+	pub fn default_ttl(mut self, ttl: Duration) -> ResponseCache {
+		self.default_ttl = Some(ttl);
+		self
+	}
+
+	/// Returns the time to live for an endpoint, or None if it isn't cached
+	pub fn ttl_for(&self, uri: &Uri) -> Option<Duration> {
+		self.ttls.iter().find(|&&(ref path, _)| path_matches(path, uri.path())).map(|&(_, ttl)| ttl).or(self.default_ttl)
+	}
+
+	/// Returns the store responses are kept in
+	pub fn store(&self) -> &CacheStore {
+		&*self.store
+	}
+
+	/// Removes the cached response for a url, so the next request to it is sent to reddit
+	pub fn invalidate(&self, uri: &Uri) {
+		self.store.remove(&uri.to_string());
+	}
+}
+
+impl Default for ResponseCache {
+	fn default() -> Self {
+		ResponseCache::new()
+	}
+}
+
+// Whether a path starts with a pattern, where `*` segments match any segment
+fn path_matches(pattern: &str, path: &str) -> bool {
+	let mut segments = path.split('/');
+	pattern.trim_end_matches('/').split('/').all(|expected| match segments.next() {
+		Some(segment) => expected == "*" || expected == segment,
+		None => false,
+	})
+}
+
+/// Adds the headers that revalidate a cached response to a request
+pub(crate) fn add_conditional_headers(headers: &mut HeaderMap, cached: &CachedResponse) {
+	if let Some(etag) = cached.etag.as_ref().and_then(|etag| HeaderValue::from_str(etag).ok()) {
+		headers.insert(header::IF_NONE_MATCH, etag);
+	}
+	if let Some(date) = cached.last_modified.as_ref().and_then(|date| HeaderValue::from_str(date).ok()) {
+		headers.insert(header::IF_MODIFIED_SINCE, date);
+	}
+}
+
+/// Makes a cached response from the headers and body of a response
+pub(crate) fn cached_response(headers: &HeaderMap, body: String, ttl: Duration) -> CachedResponse {
+	let header = |name| headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok()).map(|value| value.to_string());
+	CachedResponse {
+		body,
+		etag: header(header::ETAG),
+		last_modified: header(header::LAST_MODIFIED),
+		expires: SystemTime::now() + ttl,
+	}
+}
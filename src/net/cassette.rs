@@ -0,0 +1,313 @@
+//! # Cassettes
+//! A cassette is a file of recorded requests and the responses reddit gave to them. A
+//! `CassetteTransport` in record mode sends requests through another transport and keeps every
+//! interaction, writing them to the cassette when it is dropped. In replay mode, it answers requests with the recorded responses
+//! instead, without touching the network. This makes tests against real reddit interactions
+//! deterministic.
+//!
+//! Secrets are redacted before anything is written: the values of `Authorization` and cookie
+//! headers, passwords, refresh tokens and codes in request bodies, such as the ones sent by
+//! `OAuth::create_script`, and the tokens in responses to token requests are replaced with
+//! `REDACTED`. Replaying doesn't need them, since requests are matched by method and url only.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use failure::Error;
+use futures::{future, Future, Stream};
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use json::{self, Value};
+
+use net::transport::{HyperTransport, Transport, TransportFuture};
+
+/// The value secrets are replaced with in a cassette
+pub const REDACTED: &str = "REDACTED";
+
+// Request body parameters whose values are redacted
+const SECRET_PARAMS: &[&str] = &["password", "passwd", "client_secret", "refresh_token", "code"];
+
+// Fields of json response bodies whose values are redacted
+const SECRET_FIELDS: &[&str] = &["access_token", "refresh_token"];
+
+/// A request and its response, as stored in a cassette
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interaction {
+	/// Method of the request
+	pub method: String,
+	/// Url of the request
+	pub uri: String,
+	/// Headers of the request, with secrets redacted
+	pub request_headers: Vec<(String, String)>,
+	/// Body of the request, with secrets redacted
+	pub request_body: String,
+	/// Status of the response
+	pub status: u16,
+	/// Headers of the response
+	pub response_headers: Vec<(String, String)>,
+	/// Body of the response
+	pub response_body: String,
+}
+
+impl Interaction {
+	/// Converts the interaction to the json stored in a cassette
+	pub fn to_value(&self) -> Value {
+		json::json!({
+			"request": {
+				"method": self.method,
+				"uri": self.uri,
+				"headers": headers_to_value(&self.request_headers),
+				"body": self.request_body,
+			},
+			"response": {
+				"status": self.status,
+				"headers": headers_to_value(&self.response_headers),
+				"body": self.response_body,
+			},
+		})
+	}
+
+	/// Reads an interaction from the json stored in a cassette
+	pub fn from_value(value: &Value) -> Result<Interaction, Error> {
+		let string = |value: &Value, name: &str| -> Result<String, Error> { value.as_str().map(|value| value.to_string()).ok_or_else(|| format_err!("Cassette interaction is missing {}", name)) };
+
+		Ok(Interaction {
+			method: string(&value["request"]["method"], "request method")?,
+			uri: string(&value["request"]["uri"], "request uri")?,
+			request_headers: headers_from_value(&value["request"]["headers"]),
+			request_body: value["request"]["body"].as_str().unwrap_or("").to_string(),
+			status: value["response"]["status"].as_u64().ok_or_else(|| format_err!("Cassette interaction is missing response status"))? as u16,
+			response_headers: headers_from_value(&value["response"]["headers"]),
+			response_body: value["response"]["body"].as_str().unwrap_or("").to_string(),
+		})
+	}
+
+	fn to_response(&self) -> Response<Body> {
+		let mut response = Response::new(Body::from(self.response_body.clone()));
+		*response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+		for &(ref name, ref value) in &self.response_headers {
+			if let (Ok(name), Ok(value)) = (name.parse::<HeaderName>(), HeaderValue::from_str(value)) {
+				response.headers_mut().append(name, value);
+			}
+		}
+		response
+	}
+}
+
+fn headers_to_value(headers: &[(String, String)]) -> Value {
+	Value::Array(headers.iter().map(|&(ref name, ref value)| json::json!([name, value])).collect())
+}
+
+fn headers_from_value(value: &Value) -> Vec<(String, String)> {
+	value
+		.as_array()
+		.map(|headers| {
+			headers
+				.iter()
+				.filter_map(|header| match (header[0].as_str(), header[1].as_str()) {
+					(Some(name), Some(value)) => Some((name.to_string(), value.to_string())),
+					_ => None,
+				})
+				.collect()
+		})
+		.unwrap_or_default()
+}
+
+// Copies headers, replacing the values of the ones that hold credentials
+fn redact_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+	headers
+		.iter()
+		.map(|(name, value)| {
+			let value = if *name == header::AUTHORIZATION || *name == header::PROXY_AUTHORIZATION || *name == header::COOKIE || *name == header::SET_COOKIE {
+				REDACTED.to_string()
+			} else {
+				String::from_utf8_lossy(value.as_bytes()).into()
+			};
+			(name.as_str().to_string(), value)
+		})
+		.collect()
+}
+
+// Replaces the values of secret parameters in a urlencoded body
+fn redact_body(body: &str) -> String {
+	body.split('&')
+		.map(|param| match param.find('=') {
+			Some(i) if SECRET_PARAMS.contains(&&param[..i]) => format!("{}={}", &param[..i], REDACTED),
+			_ => param.to_string(),
+		})
+		.collect::<Vec<_>>()
+		.join("&")
+}
+
+// Replaces the values of secret fields in a json body. Other bodies are returned unchanged.
+fn redact_response_body(body: &str) -> String {
+	fn redact(value: &mut Value) -> bool {
+		match *value {
+			Value::Object(ref mut fields) => {
+				let mut redacted = false;
+				for (name, field) in fields.iter_mut() {
+					if SECRET_FIELDS.contains(&name.as_str()) && field.is_string() {
+						*field = Value::from(REDACTED);
+						redacted = true;
+					} else {
+						redacted |= redact(field);
+					}
+				}
+				redacted
+			}
+			Value::Array(ref mut values) => values.iter_mut().fold(false, |redacted, value| redact(value) || redacted),
+			_ => false,
+		}
+	}
+
+	match json::from_str::<Value>(body) {
+		Ok(mut value) => {
+			if redact(&mut value) {
+				value.to_string()
+			} else {
+				body.to_string()
+			}
+		}
+		Err(_) => body.to_string(),
+	}
+}
+
+/// Loads the interactions of a cassette file
+pub fn load(path: &Path) -> Result<Vec<Interaction>, Error> {
+	let mut contents = String::new();
+	File::open(path)?.read_to_string(&mut contents)?;
+	let value: Value = json::from_str(&contents)?;
+
+	value["interactions"].as_array().ok_or_else(|| format_err!("Cassette {} has no interactions", path.display()))?.iter().map(Interaction::from_value).collect()
+}
+
+/// Writes interactions to a cassette file, replacing its contents
+pub fn save(path: &Path, interactions: &[Interaction]) -> Result<(), Error> {
+	let value = json::json!({ "interactions": interactions.iter().map(Interaction::to_value).collect::<Vec<_>>() });
+	File::create(path)?.write_all(json::to_string_pretty(&value)?.as_bytes())?;
+	Ok(())
+}
+
+enum Mode {
+	Record { inner: Arc<Transport>, path: PathBuf },
+	Replay,
+}
+
+/// A transport that records interactions to a cassette file, or replays them from one
+pub struct CassetteTransport {
+	mode: Mode,
+	// Recorded interactions, and whether each has been replayed already
+	interactions: Arc<Mutex<Vec<(Interaction, bool)>>>,
+}
+
+impl CassetteTransport {
+	/// Creates a transport that sends requests through another transport and records them. The
+	/// cassette file is written when the transport is dropped, which happens when the last app or
+	/// connection using it is dropped.
+	/// # Arguments
+	/// * `path` - The cassette file to record to
+	/// * `inner` - The transport that actually sends the requests
+	pub fn record<P: AsRef<Path>, T: Transport + 'static>(path: P, inner: T) -> CassetteTransport {
+		CassetteTransport {
+			mode: Mode::Record { inner: Arc::new(inner), path: path.as_ref().to_path_buf() },
+			interactions: Arc::new(Mutex::new(Vec::new())),
+		}
+	}
+
+	/// Creates a transport that answers requests with the responses recorded in a cassette file.
+	/// Each request is answered by the first interaction with the same method and url that hasn't
+	/// been replayed yet. Requests without a matching interaction fail.
+	/// # Arguments
+	/// * `path` - The cassette file to replay
+	pub fn replay<P: AsRef<Path>>(path: P) -> Result<CassetteTransport, Error> {
+		let interactions = load(path.as_ref())?;
+		Ok(CassetteTransport {
+			mode: Mode::Replay,
+			interactions: Arc::new(Mutex::new(interactions.into_iter().map(|interaction| (interaction, false)).collect())),
+		})
+	}
+
+	/// Replays a cassette file if it exists, or records a new one to it over the network otherwise
+	/// # Arguments
+	/// * `path` - The cassette file to replay or record to
+	pub fn record_or_replay<P: AsRef<Path>>(path: P) -> Result<CassetteTransport, Error> {
+		if path.as_ref().exists() {
+			CassetteTransport::replay(path)
+		} else {
+			Ok(CassetteTransport::record(path, HyperTransport::new()?))
+		}
+	}
+
+	/// Returns the interactions recorded or replayed so far
+	pub fn interactions(&self) -> Vec<Interaction> {
+		let interactions = self.interactions.lock().unwrap();
+		match self.mode {
+			Mode::Record { .. } => interactions.iter().map(|&(ref interaction, _)| interaction.clone()).collect(),
+			Mode::Replay => interactions.iter().filter(|&&(_, replayed)| replayed).map(|&(ref interaction, _)| interaction.clone()).collect(),
+		}
+	}
+}
+
+// Writes the recorded interactions once recording is over, so requests don't wait for the file
+impl Drop for CassetteTransport {
+	fn drop(&mut self) {
+		if let Mode::Record { ref path, .. } = self.mode {
+			let recorded: Vec<Interaction> = self.interactions.lock().unwrap().iter().map(|&(ref interaction, _)| interaction.clone()).collect();
+			if let Err(e) = save(path, &recorded) {
+				warn!("Could not save cassette {}: {}", path.display(), e);
+			}
+		}
+	}
+}
+
+impl Transport for CassetteTransport {
+	fn send(&self, req: Request<Body>) -> TransportFuture {
+		match self.mode {
+			Mode::Replay => {
+				let method = req.method().to_string();
+				let uri = req.uri().to_string();
+				let mut interactions = self.interactions.lock().unwrap();
+				match interactions.iter_mut().find(|&&mut (ref interaction, replayed)| !replayed && interaction.method == method && interaction.uri == uri) {
+					Some(&mut (ref interaction, ref mut replayed)) => {
+						*replayed = true;
+						trace!("Replaying {} {} from cassette", method, uri);
+						Box::new(future::ok(interaction.to_response()))
+					}
+					None => Box::new(future::err(format_err!("No recorded interaction for {} {}", method, uri))),
+				}
+			}
+			Mode::Record { ref inner, .. } => {
+				let (inner, interactions) = (Arc::clone(inner), Arc::clone(&self.interactions));
+				let (parts, body) = req.into_parts();
+				let method = parts.method.to_string();
+				let uri = parts.uri.to_string();
+				let request_headers = redact_headers(&parts.headers);
+
+				// Both bodies are read into memory so they can be recorded and still be passed on
+				Box::new(body.concat2().from_err().and_then(move |body| {
+					let request_body = redact_body(&String::from_utf8_lossy(&body));
+					inner.send(Request::from_parts(parts, Body::from(body))).and_then(move |response| {
+						let (parts, body) = response.into_parts();
+						body.concat2().from_err().and_then(move |body| {
+							let interaction = Interaction {
+								method,
+								uri,
+								request_headers,
+								request_body,
+								status: parts.status.as_u16(),
+								response_headers: redact_headers(&parts.headers),
+								response_body: redact_response_body(&String::from_utf8_lossy(&body)),
+							};
+							trace!("Recording {} {} to cassette", interaction.method, interaction.uri);
+							interactions.lock().unwrap().push((interaction, true));
+
+							Ok(Response::from_parts(parts, Body::from(body)))
+						})
+					})
+				}))
+			}
+		}
+	}
+}
@@ -0,0 +1,134 @@
+//! # Endpoints
+//! An `Endpoint` describes a reddit API endpoint: its method and path, the parameters it takes,
+//! whether it needs authorization, and what its response is parsed into. `Connection::execute`
+//! sends any endpoint with the same ratelimiting, authorization, retries and error handling as the
+//! methods of `App`, so endpoints orca hasn't wrapped yet can be called without building requests
+//! by hand.
+//!
+//! ```rust,no_run
+//! # extern crate failure;
+//! # extern crate orca;
+//! # extern crate serde_json;
+//! # use failure::Error;
+//! # use orca::net::endpoint::Endpoint;
+//! # use serde_json::Value;
+//! /// The karma of a user
+//! struct Karma {
+//! 	user: String,
+//! }
+//!
+//! impl Endpoint for Karma {
+//! 	type Response = i64;
+//!
+//! 	fn path(&self) -> &str {
+//! 		"/user/{name}/about/.json"
+//! 	}
+//!
+//! 	fn path_params(&self) -> Vec<(&str, String)> {
+//! 		vec![("name", self.user.clone())]
+//! 	}
+//!
+//! 	fn parse(&self, response: Value) -> Result<i64, Error> {
+//! 		response["data"]["link_karma"].as_i64().ok_or_else(|| failure::err_msg("Response has no karma"))
+//! 	}
+//! }
+//!
+//! # fn main() {
+//! # let reddit = orca::App::new("a", "b", "c").unwrap();
+//! let karma = reddit.execute(Karma { user: "IntrepidPig".to_string() }).unwrap();
+//! # }
+//! ```
+
+use failure::Error;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Method, Request};
+use json::Value;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use url::Url;
+
+use net::form::{self, Form};
+use net::Connection;
+
+/// A reddit API endpoint. Only the path and how to parse the response have to be given; the
+/// defaults describe a GET request without parameters that doesn't need authorization.
+pub trait Endpoint {
+	/// The type the response is parsed into
+	type Response: Send + 'static;
+
+	/// The method of the request
+	fn method(&self) -> Method {
+		Method::GET
+	}
+
+	/// The path of the endpoint, such as `/user/{name}/about/.json`. Parts in braces are replaced
+	/// with the path parameters of the same name.
+	fn path(&self) -> &str;
+
+	/// The values to fill into the path, by name. Values are encoded, so they can be passed as raw
+	/// strings.
+	fn path_params(&self) -> Vec<(&str, String)> {
+		Vec::new()
+	}
+
+	/// The query parameters of the request, not encoded
+	fn query(&self) -> Vec<(String, String)> {
+		Vec::new()
+	}
+
+	/// The form body of the request, if it has one
+	fn form(&self) -> Option<Form> {
+		None
+	}
+
+	/// The OAuth scope the endpoint requires, such as `identity`. This is only informational, as
+	/// reddit checks the scopes of a token itself.
+	fn scope(&self) -> Option<&str> {
+		None
+	}
+
+	/// Whether the request has to be authorized. Authorized requests are sent to the OAuth base url
+	/// with the token of the connection, others to the public base url. By default, endpoints that
+	/// require a scope require authorization.
+	fn requires_auth(&self) -> bool {
+		self.scope().is_some()
+	}
+
+	/// Parses the json response of the endpoint. Errors reported by reddit have already been turned
+	/// into a `RedditError` at this point.
+	/// # Arguments
+	/// * `response` - The json body of the response
+	fn parse(&self, response: Value) -> Result<Self::Response, Error>;
+}
+
+/// Builds the request for an endpoint
+/// # Arguments
+/// * `conn` - The connection whose base urls to use
+/// * `endpoint` - The endpoint to build the request for
+pub fn request<E: Endpoint + ?Sized>(conn: &Connection, endpoint: &E) -> Result<Request<Body>, Error> {
+	let mut path = endpoint.path().to_string();
+	for (name, value) in endpoint.path_params() {
+		let placeholder = format!("{{{}}}", name);
+		if !path.contains(&placeholder) {
+			return Err(format_err!("Path {} has no parameter {}", endpoint.path(), name));
+		}
+		path = path.replace(&placeholder, &utf8_percent_encode(&value, PATH_SEGMENT_ENCODE_SET).to_string());
+	}
+	if path.contains('{') {
+		return Err(format_err!("Path {} is missing parameters", path));
+	}
+
+	let base = if endpoint.requires_auth() { conn.oauth_url(&path) } else { conn.public_url(&path) };
+	let query = endpoint.query();
+	let url = if query.is_empty() { Url::parse(&base)? } else { Url::parse_with_params(&base, &query)? };
+
+	let (body, content_type) = match endpoint.form() {
+		Some(form) => (Body::from(form), Some(form::CONTENT_TYPE)),
+		None => (Body::empty(), None),
+	};
+	let mut req = Request::builder().method(endpoint.method()).uri(url.as_str()).body(body)?;
+	if let Some(content_type) = content_type {
+		req.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+	}
+
+	Ok(req)
+}
@@ -0,0 +1,58 @@
+//! # Form bodies
+//! Reddit takes the parameters of POST requests as an `application/x-www-form-urlencoded` body. A
+//! `Form` builds such a body from raw names and values, and encodes all of them, so text containing
+//! characters like `&`, `+` or `=` arrives at reddit the way it was written.
+
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Request};
+use url::form_urlencoded;
+
+/// The content type of a form body
+pub const CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+
+/// The parameters of a form-urlencoded request body, kept in the order they were added
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Form {
+	params: Vec<(String, String)>,
+}
+
+impl Form {
+	/// Creates a form without parameters
+	pub fn new() -> Form {
+		Form::default()
+	}
+
+	/// Adds a parameter
+	/// # Arguments
+	/// * `name` - Name of the parameter, not encoded
+	/// * `value` - Value of the parameter, not encoded
+	pub fn param(mut self, name: &str, value: &str) -> Form {
+		self.params.push((name.to_string(), value.to_string()));
+		self
+	}
+
+	/// Returns the parameters added so far, not encoded
+	pub fn params(&self) -> &[(String, String)] {
+		&self.params
+	}
+
+	/// Encodes the form as the contents of a request body
+	pub fn encode(&self) -> String {
+		form_urlencoded::Serializer::new(String::new()).extend_pairs(&self.params).finish()
+	}
+
+	/// Creates a POST request with the form as its body
+	/// # Arguments
+	/// * `url` - The url to send the request to
+	pub fn post(self, url: &str) -> Request<Body> {
+		let mut req = Request::post(url).body(Body::from(self)).unwrap();
+		req.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+		req
+	}
+}
+
+impl From<Form> for Body {
+	fn from(form: Form) -> Body {
+		Body::from(form.encode())
+	}
+}
@@ -0,0 +1,234 @@
+//! # Metrics
+//! A `Metrics` registry counts what a connection does: the requests it sends by endpoint and
+//! status, how long they take, how often they are retried, how long the rate limiter makes them
+//! wait, the remaining ratelimit budget, token refreshes and response cache hits. A registry is
+//! added to a connection with `ConnectionBuilder::metrics`, and can be shared between connections.
+//!
+//! The metrics are exported in the Prometheus text format by `Metrics::prometheus`, which can be
+//! served from any HTTP server.
+//!
+//! Endpoints are labeled by the path of the request without its query, with the names and ids in
+//! it replaced by placeholders, such as `/user/{name}/about` or `/r/{sub}/comments/{id}`, so the
+//! amount of series doesn't grow with every user or post a program visits.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hyper::{Method, Uri};
+
+/// The upper bounds of the latency histogram buckets, in seconds
+pub const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// Method and path template of a request
+type Endpoint = (String, String);
+
+#[derive(Debug, Default)]
+struct Histogram {
+	// Counts of observations per bucket, not cumulative
+	buckets: Vec<u64>,
+	sum: f64,
+	count: u64,
+}
+
+impl Histogram {
+	fn observe(&mut self, value: f64) {
+		if self.buckets.is_empty() {
+			self.buckets = vec![0; LATENCY_BUCKETS.len()];
+		}
+		if let Some(i) = LATENCY_BUCKETS.iter().position(|bound| value <= *bound) {
+			self.buckets[i] += 1;
+		}
+		self.sum += value;
+		self.count += 1;
+	}
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+	requests: BTreeMap<(Endpoint, String), u64>,
+	latency: BTreeMap<Endpoint, Histogram>,
+	retries: BTreeMap<Endpoint, u64>,
+	ratelimit_sleep: f64,
+	ratelimit_remaining: Option<i32>,
+	ratelimit_used: Option<i32>,
+	refreshes: BTreeMap<&'static str, u64>,
+	cache: BTreeMap<&'static str, u64>,
+}
+
+/// A registry of metrics about the requests of one or more connections
+#[derive(Debug, Default)]
+pub struct Metrics {
+	registry: Mutex<Registry>,
+}
+
+impl Metrics {
+	/// Creates an empty registry
+	pub fn new() -> Metrics {
+		Metrics::default()
+	}
+
+	/// Records a finished request
+	/// # Arguments
+	/// * `method` - Method of the request
+	/// * `uri` - Uri of the request
+	/// * `status` - The status code of the response, or None if the request failed without one
+	/// * `elapsed` - How long the request took
+	pub fn record_request(&self, method: &Method, uri: &Uri, status: Option<u16>, elapsed: Duration) {
+		let endpoint = endpoint(method, uri);
+		let status = status.map(|status| status.to_string()).unwrap_or_else(|| "error".to_string());
+		let mut registry = self.registry.lock().unwrap();
+		*registry.requests.entry((endpoint.clone(), status)).or_insert(0) += 1;
+		registry.latency.entry(endpoint).or_insert_with(Histogram::default).observe(secs(elapsed));
+	}
+
+	/// Records that a request is being retried
+	pub fn record_retry(&self, method: &Method, uri: &Uri) {
+		*self.registry.lock().unwrap().retries.entry(endpoint(method, uri)).or_insert(0) += 1;
+	}
+
+	/// Records time spent waiting for the rate limiter
+	pub fn record_ratelimit_sleep(&self, wait: Duration) {
+		self.registry.lock().unwrap().ratelimit_sleep += secs(wait);
+	}
+
+	/// Records the ratelimit budget reported by a response
+	pub fn record_ratelimit(&self, used: Option<i32>, remaining: Option<i32>) {
+		let mut registry = self.registry.lock().unwrap();
+		if used.is_some() {
+			registry.ratelimit_used = used;
+		}
+		if remaining.is_some() {
+			registry.ratelimit_remaining = remaining;
+		}
+	}
+
+	/// Records a token refresh
+	/// # Arguments
+	/// * `success` - Whether the refresh succeeded
+	pub fn record_refresh(&self, success: bool) {
+		*self.registry.lock().unwrap().refreshes.entry(if success { "success" } else { "failure" }).or_insert(0) += 1;
+	}
+
+	/// Records a request that could be answered from the response cache
+	/// # Arguments
+	/// * `result` - `hit` if the cached response was used without a request, `revalidated` if reddit
+	/// confirmed it is unchanged, or `miss` if a new response had to be fetched
+	pub fn record_cache(&self, result: &'static str) {
+		*self.registry.lock().unwrap().cache.entry(result).or_insert(0) += 1;
+	}
+
+	/// Exports all metrics in the Prometheus text format
+	pub fn prometheus(&self) -> String {
+		let registry = self.registry.lock().unwrap();
+		let mut out = String::new();
+
+		header(&mut out, "orca_requests_total", "counter", "Requests sent to reddit, by endpoint and response status");
+		for (&((ref method, ref path), ref status), count) in &registry.requests {
+			let _ = writeln!(out, "orca_requests_total{{method=\"{}\",endpoint=\"{}\",status=\"{}\"}} {}", escape(method), escape(path), escape(status), count);
+		}
+
+		header(&mut out, "orca_request_duration_seconds", "histogram", "Time from sending a request until its response was read");
+		for (&(ref method, ref path), histogram) in &registry.latency {
+			let labels = format!("method=\"{}\",endpoint=\"{}\"", escape(method), escape(path));
+			let mut cumulative = 0;
+			for (bound, count) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+				cumulative += count;
+				let _ = writeln!(out, "orca_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound, cumulative);
+			}
+			let _ = writeln!(out, "orca_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, histogram.count);
+			let _ = writeln!(out, "orca_request_duration_seconds_sum{{{}}} {}", labels, histogram.sum);
+			let _ = writeln!(out, "orca_request_duration_seconds_count{{{}}} {}", labels, histogram.count);
+		}
+
+		header(&mut out, "orca_retries_total", "counter", "Requests retried after a transient failure, by endpoint");
+		for (&(ref method, ref path), count) in &registry.retries {
+			let _ = writeln!(out, "orca_retries_total{{method=\"{}\",endpoint=\"{}\"}} {}", escape(method), escape(path), count);
+		}
+
+		header(&mut out, "orca_ratelimit_sleep_seconds_total", "counter", "Time spent waiting for the rate limiter");
+		let _ = writeln!(out, "orca_ratelimit_sleep_seconds_total {}", registry.ratelimit_sleep);
+
+		if let Some(used) = registry.ratelimit_used {
+			header(&mut out, "orca_ratelimit_used", "gauge", "Requests used in the current ratelimit period");
+			let _ = writeln!(out, "orca_ratelimit_used {}", used);
+		}
+		if let Some(remaining) = registry.ratelimit_remaining {
+			header(&mut out, "orca_ratelimit_remaining", "gauge", "Requests remaining in the current ratelimit period");
+			let _ = writeln!(out, "orca_ratelimit_remaining {}", remaining);
+		}
+
+		header(&mut out, "orca_token_refreshes_total", "counter", "Access token refreshes, by result");
+		for (result, count) in &registry.refreshes {
+			let _ = writeln!(out, "orca_token_refreshes_total{{result=\"{}\"}} {}", result, count);
+		}
+
+		header(&mut out, "orca_cache_requests_total", "counter", "Requests to cached endpoints, by whether the cache answered them");
+		for (result, count) in &registry.cache {
+			let _ = writeln!(out, "orca_cache_requests_total{{result=\"{}\"}} {}", result, count);
+		}
+
+		out
+	}
+}
+
+fn endpoint(method: &Method, uri: &Uri) -> Endpoint {
+	(method.to_string(), template(uri.path()))
+}
+
+// Replaces the names and ids in a path with placeholders, so the paths of the same endpoint share
+// one series
+fn template(path: &str) -> String {
+	let mut segments = Vec::new();
+	// Placeholders for the segments following a segment like `user`
+	let mut pending: Vec<&str> = Vec::new();
+
+	for segment in path.split('/') {
+		if segment.is_empty() || segment == ".json" {
+			segments.push(segment.to_string());
+			continue;
+		}
+		let (name, suffix) = if segment.ends_with(".json") { (&segment[..segment.len() - 5], ".json") } else { (segment, "") };
+
+		if !pending.is_empty() {
+			segments.push(format!("{{{}}}{}", pending.remove(0), suffix));
+		} else if is_fullname(name) {
+			segments.push(format!("{{fullname}}{}", suffix));
+		} else {
+			pending = match name {
+				"user" | "u" => vec!["name"],
+				"r" => vec!["sub"],
+				"comments" => vec!["id", "title", "comment"],
+				"by_id" => vec!["names"],
+				"duplicates" => vec!["id"],
+				"m" => vec!["multi"],
+				"wiki" => vec!["page"],
+				_ => Vec::new(),
+			};
+			segments.push(segment.to_string());
+		}
+	}
+
+	segments.join("/")
+}
+
+// Whether a segment is the fullname of a thing, such as `t3_7le01h`
+fn is_fullname(segment: &str) -> bool {
+	let bytes = segment.as_bytes();
+	bytes.len() > 3 && bytes[0] == b't' && bytes[1].is_ascii_digit() && bytes[2] == b'_'
+}
+
+fn secs(duration: Duration) -> f64 {
+	duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+	let _ = writeln!(out, "# HELP {} {}", name, help);
+	let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+// Escapes a label value as described by the Prometheus text format
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
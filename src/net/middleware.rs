@@ -0,0 +1,49 @@
+//! # Middleware
+//! Middleware observes and modifies the traffic of a connection. Every request passes through the
+//! middleware chain of its connection right before it is sent, after ratelimiting, authorization
+//! and the user agent have been handled, and every response passes back through the chain once its
+//! body has been read. Retried requests pass through the chain again on each attempt.
+//!
+//! Requests are given to middleware in the order it was added to the connection, and responses in
+//! the reverse order, so the first middleware added sees the request first and the response last.
+
+use std::time::Duration;
+
+use failure::Error;
+use hyper::{Request, Response};
+
+use net::ratelimit::RatelimitHeaders;
+
+/// Information about a response besides its contents
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResponseInfo {
+	/// The time from sending the request until the whole body of the response was read
+	pub elapsed: Duration,
+	/// The ratelimit headers of the response
+	pub ratelimit: RatelimitHeaders,
+}
+
+/// Something that observes or modifies requests and their responses. All methods do nothing by
+/// default, so only the ones that are needed have to be implemented.
+pub trait Middleware: Send + Sync {
+	/// Called right before a request is sent. The request can be changed, such as by adding headers.
+	/// # Arguments
+	/// * `req` - The request, with its body read into memory
+	fn on_request(&self, _req: &mut Request<Vec<u8>>) {}
+
+	/// Called once the body of a response has been read, before it is checked for errors and parsed.
+	/// The response can be changed, such as by redacting its body.
+	/// # Arguments
+	/// * `req` - The request the response answers, as it was sent
+	/// * `res` - The response, with its body read into memory
+	/// * `info` - How long the response took, and its ratelimit headers
+	fn on_response(&self, _req: &Request<Vec<u8>>, _res: &mut Response<String>, _info: &ResponseInfo) {}
+
+	/// Called when a request failed without a complete response, such as when the connection failed
+	/// or timed out
+	/// # Arguments
+	/// * `req` - The request that failed, as it was sent
+	/// * `error` - Why the request failed
+	/// * `elapsed` - The time from sending the request until it failed
+	fn on_error(&self, _req: &Request<Vec<u8>>, _error: &Error, _elapsed: Duration) {}
+}
@@ -6,27 +6,75 @@
 
 /// Contains all functionality for OAuth and logins
 pub mod auth;
+/// Contains the cache for responses to read requests
+pub mod cache;
+/// Contains the transport for recording and replaying interactions
+pub mod cassette;
+/// Contains the trait describing reddit API endpoints
+pub mod endpoint;
+/// Contains the builder for form-encoded request bodies
+pub mod form;
+/// Contains the metrics registry and its Prometheus export
+pub mod metrics;
+/// Contains the middleware requests and responses pass through
+pub mod middleware;
+/// Contains the proxies requests can be sent through
+pub mod proxy;
+/// Contains the rate limiters deciding how long to wait before each request
+pub mod ratelimit;
+/// Contains the policy for retrying failed requests
+pub mod retry;
+/// Contains the timeouts of requests and cancellation
+pub mod timeout;
+/// Contains the stores keeping authorization info between runs
+pub mod tokenstore;
+/// Contains the transports requests can be sent with
+pub mod transport;
+/// Contains the user agent requests are sent with
+pub mod useragent;
 
-use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::error::Error as StdError;
 use std::hash::BuildHasher;
-use std::thread;
-use std::time::{Duration, Instant};
-
-use futures::Stream;
-use hyper::client::{Client, HttpConnector};
-use hyper::header::{self, HeaderValue};
-use hyper::{Body, Request, Response, Uri};
-use hyper_tls::HttpsConnector;
+use std::io;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::future::{self, Loop, Shared as SharedFuture};
+use futures::{Future, Stream};
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{self, Body, Chunk, Method, Request, Response, StatusCode, Uri, Version};
 use json;
 use json::Value;
-use tokio_core::reactor::Core;
+use tokio::timer::timeout::Error as TimeoutError;
+use tokio::timer::{Delay, Timeout};
 
 use self::auth::OAuth;
-use errors::RedditError;
+use self::cache::ResponseCache;
+use self::endpoint::Endpoint;
+use self::form::Form;
+use self::metrics::Metrics;
+use self::middleware::{Middleware, ResponseInfo};
+use self::proxy::Proxy;
+use self::ratelimit::{BurstLimiter, RateLimiter, RatelimitHeaders, RatelimitStatus, SteadyLimiter};
+use self::retry::RetryPolicy;
+use self::timeout::Timeouts;
+use self::tokenstore::TokenStore;
+use self::transport::{HyperTransport, HyperTransportBuilder, Transport, TransportFuture};
+use self::useragent::UserAgent;
+use errors::{RedditError, TimeoutKind};
 
 use failure::Error;
 
+/// A boxed future resolving to the result of a request to reddit
+pub type RedditFuture<T> = Box<Future<Item = T, Error = Error> + Send>;
+
+/// A boxed stream of items retrieved from reddit
+pub type RedditStream<T> = Box<Stream<Item = T, Error = Error> + Send>;
+
+// A token refresh that is in progress, which every request waiting for the new token shares
+type RefreshFuture = SharedFuture<Box<Future<Item = String, Error = String> + Send>>;
+
 /// How to ratelimit
 #[derive(Copy, Clone)]
 pub enum LimitMethod {
@@ -37,25 +85,311 @@ pub enum LimitMethod {
 	Burst,
 }
 
+/// The base urls a connection sends its requests to. By default these point to reddit.com, but they
+/// can be changed to talk to any server implementing the reddit API, such as a local mock server.
+#[derive(Debug, Clone)]
+pub struct BaseUrls {
+	/// Base url for requests that don't require authorization
+	pub public: String,
+	/// Base url for requests that require OAuth authorization
+	pub oauth: String,
+	/// Base url for requesting access tokens
+	pub token: String,
+}
+
+impl BaseUrls {
+	/// Creates a set of base urls. Trailing slashes are removed.
+	/// # Arguments
+	/// * `public` - Base url for requests that don't require authorization
+	/// * `oauth` - Base url for requests that require OAuth authorization
+	/// * `token` - Base url for requesting access tokens
+	pub fn new(public: &str, oauth: &str, token: &str) -> BaseUrls {
+		BaseUrls {
+			public: public.trim_end_matches('/').to_string(),
+			oauth: oauth.trim_end_matches('/').to_string(),
+			token: token.trim_end_matches('/').to_string(),
+		}
+	}
+
+	/// Creates a set of base urls that all point to the same server
+	pub fn all(base: &str) -> BaseUrls {
+		BaseUrls::new(base, base, base)
+	}
+}
+
+impl Default for BaseUrls {
+	fn default() -> Self {
+		BaseUrls::new("https://www.reddit.com", "https://oauth.reddit.com", "https://ssl.reddit.com")
+	}
+}
+
+/// A builder for a `Connection`, for when the defaults used by `Connection::new` aren't enough.
+pub struct ConnectionBuilder {
+	useragent: UserAgent,
+	urls: BaseUrls,
+	transport: Option<Box<Transport>>,
+	retry: RetryPolicy,
+	throttle_wait: Option<Duration>,
+	limiter: Arc<RateLimiter>,
+	low_ratelimit: Option<LowRatelimit>,
+	http: HyperTransportBuilder,
+	timeouts: Timeouts,
+	middleware: Vec<Box<Middleware>>,
+	metrics: Option<Arc<Metrics>>,
+	cache: Option<ResponseCache>,
+	token_store: Option<Box<TokenStore>>,
+}
+
+impl ConnectionBuilder {
+	/// Creates a new connection builder with the default settings
+	/// # Arguments
+	/// * `appname` - The name of the app
+	/// * `appversion` - The version of the app
+	/// * `appauthor` - The reddit username of the author of the app, with or without `/u/`
+	pub fn new(appname: &str, appversion: &str, appauthor: &str) -> ConnectionBuilder {
+		ConnectionBuilder {
+			useragent: UserAgent::new(appname, appversion, appauthor),
+			urls: BaseUrls::default(),
+			transport: None,
+			retry: RetryPolicy::default(),
+			throttle_wait: None,
+			limiter: Arc::new(SteadyLimiter::new()),
+			low_ratelimit: None,
+			http: HyperTransport::builder(),
+			timeouts: Timeouts::default(),
+			middleware: Vec::new(),
+			metrics: None,
+			cache: None,
+			token_store: None,
+		}
+	}
+
+	/// Sets the user agent, replacing the one made from the app name, version and author. It is
+	/// validated when the connection is built.
+	pub fn useragent(mut self, useragent: UserAgent) -> ConnectionBuilder {
+		self.useragent = useragent;
+		self
+	}
+
+	/// Sets all base urls at once
+	pub fn base_urls(mut self, urls: BaseUrls) -> ConnectionBuilder {
+		self.urls = urls;
+		self
+	}
+
+	/// Sets the base url for requests that don't require authorization
+	pub fn public_url(mut self, url: &str) -> ConnectionBuilder {
+		self.urls.public = url.trim_end_matches('/').to_string();
+		self
+	}
+
+	/// Sets the base url for requests that require OAuth authorization
+	pub fn oauth_url(mut self, url: &str) -> ConnectionBuilder {
+		self.urls.oauth = url.trim_end_matches('/').to_string();
+		self
+	}
+
+	/// Sets the base url for requesting access tokens
+	pub fn token_url(mut self, url: &str) -> ConnectionBuilder {
+		self.urls.token = url.trim_end_matches('/').to_string();
+		self
+	}
+
+	/// Sets the transport requests are sent with. By default a `HyperTransport` is used.
+	pub fn transport<T: Transport + 'static>(mut self, transport: T) -> ConnectionBuilder {
+		self.transport = Some(Box::new(transport));
+		self
+	}
+
+	/// Sets a proxy to send all requests through, including token requests. This only applies to
+	/// the default transport, and is ignored if another transport is set.
+	pub fn proxy(mut self, proxy: Proxy) -> ConnectionBuilder {
+		self.http.proxy = Some(proxy);
+		self
+	}
+
+	/// Sets whether connections are kept open to be reused by later requests (enabled by default).
+	/// This only applies to the default transport.
+	pub fn keep_alive(mut self, keep_alive: bool) -> ConnectionBuilder {
+		self.http.keep_alive = keep_alive;
+		self
+	}
+
+	/// Sets the most idle connections to keep open to a single host (unlimited by default). This
+	/// only applies to the default transport.
+	pub fn pool_max_idle(mut self, max_idle: usize) -> ConnectionBuilder {
+		self.http.pool_max_idle = max_idle;
+		self
+	}
+
+	/// Sets how long an idle connection is kept open (90 seconds by default), or None to keep it
+	/// open until reddit closes it. This only applies to the default transport.
+	pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> ConnectionBuilder {
+		self.http.pool_idle_timeout = timeout;
+		self
+	}
+
+	/// Sets whether to use HTTP/2 when reddit supports it, which sends all requests over a single
+	/// connection (disabled by default). This only applies to the default transport.
+	pub fn http2(mut self, http2: bool) -> ConnectionBuilder {
+		self.http.http2 = http2;
+		self
+	}
+
+	/// Sets the amount of threads resolving host names (4 by default). This only applies to the
+	/// default transport.
+	pub fn dns_threads(mut self, threads: usize) -> ConnectionBuilder {
+		self.http.dns_threads = threads;
+		self
+	}
+
+	/// Sets how long requests may take. By default `Timeouts::default()` is used. The connect
+	/// timeout only applies to the default transport.
+	pub fn timeouts(mut self, timeouts: Timeouts) -> ConnectionBuilder {
+		self.timeouts = timeouts;
+		self
+	}
+
+	/// Adds a middleware to the end of the chain every request and response passes through
+	pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> ConnectionBuilder {
+		self.middleware.push(Box::new(middleware));
+		self
+	}
+
+	/// Sets a registry to record metrics about requests in. By default no metrics are recorded. To
+	/// collect the metrics of several connections together, pass the same `Arc` to each of them.
+	pub fn metrics(mut self, metrics: Arc<Metrics>) -> ConnectionBuilder {
+		self.metrics = Some(metrics);
+		self
+	}
+
+	/// Sets a cache for responses to GET requests. By default nothing is cached.
+	pub fn cache(mut self, cache: ResponseCache) -> ConnectionBuilder {
+		self.cache = Some(cache);
+		self
+	}
+
+	/// Sets a store to save the authorization info in whenever it changes, such as when a token is
+	/// refreshed. By default authorization info isn't saved.
+	pub fn token_store<S: TokenStore + 'static>(mut self, store: S) -> ConnectionBuilder {
+		self.token_store = Some(Box::new(store));
+		self
+	}
+
+	/// Sets the policy for retrying requests that failed with a transient error. By default
+	/// `RetryPolicy::default()` is used.
+	pub fn retry_policy(mut self, retry: RetryPolicy) -> ConnectionBuilder {
+		self.retry = retry;
+		self
+	}
+
+	/// Sets the rate limiter deciding how long to wait before each request. By default a
+	/// `SteadyLimiter` is used. To share a limiter between connections, pass the same `Arc` to each
+	/// of them.
+	pub fn ratelimiter<R: RateLimiter + 'static>(mut self, limiter: R) -> ConnectionBuilder {
+		self.limiter = Arc::new(limiter);
+		self
+	}
+
+	/// Sets a callback that is called when the remaining ratelimit budget drops below a threshold.
+	/// It is called once each time the budget drops, with the ratelimit status at that moment, on
+	/// whatever thread handled the response.
+	/// # Arguments
+	/// * `threshold` - The amount of remaining requests below which the callback is called
+	/// * `callback` - The function to call
+	pub fn on_low_ratelimit<F: Fn(&RatelimitStatus) + Send + Sync + 'static>(mut self, threshold: i32, callback: F) -> ConnectionBuilder {
+		self.low_ratelimit = Some(LowRatelimit { threshold, callback: Box::new(callback) });
+		self
+	}
+
+	/// Makes submissions that reddit throttles (answering "you are doing that too much") wait and
+	/// resubmit automatically, as long as the total wait for a submission stays under `max_wait`.
+	/// By default throttled submissions fail with `RedditError::Ratelimited` instead.
+	pub fn throttle_wait(mut self, max_wait: Duration) -> ConnectionBuilder {
+		self.throttle_wait = Some(max_wait);
+		self
+	}
+
+	/// Creates the connection. Fails with `InvalidUserAgent` if the user agent doesn't follow
+	/// reddit's rules.
+	pub fn build(self) -> Result<Connection, Error> {
+		let useragent = self.useragent.to_header()?;
+		let transport = match self.transport {
+			Some(transport) => transport,
+			None => {
+				let mut transport = self.http;
+				transport.connect_timeout = self.timeouts.connect;
+				Box::new(transport.build()?)
+			}
+		};
+		Ok(Connection {
+			shared: Arc::new(Shared {
+				auth: RwLock::new(None),
+				refreshing: Mutex::new(None),
+				useragent,
+				urls: self.urls,
+				transport,
+				retry: self.retry,
+				throttle_wait: Mutex::new(self.throttle_wait),
+				limiter: RwLock::new(self.limiter),
+				status: Mutex::new(RatelimitStatus::default()),
+				low_ratelimit: self.low_ratelimit,
+				middleware: self.middleware,
+				metrics: self.metrics,
+				cache: self.cache,
+				token_store: self.token_store,
+			}),
+			timeouts: self.timeouts,
+		})
+	}
+}
+
 /// A connection holder to reddit. Holds authorization info if provided, and is in charge
 /// of ratelimiting.
+///
+/// Cloning a connection is cheap, and all clones share the same authorization and ratelimiting
+/// state. A connection can be shared between threads. All requests return futures, which are run
+/// by the caller.
+#[derive(Clone)]
 pub struct Connection {
+	shared: Arc<Shared>,
+	timeouts: Timeouts,
+}
+
+struct Shared {
 	/// Authorization info (optional, but required for sending authorized requests)
-	pub auth: Option<auth::OAuth>,
+	auth: RwLock<Option<OAuth>>,
+	/// Token refresh in progress, if any
+	refreshing: Mutex<Option<RefreshFuture>>,
 	/// User agent for the client
-	pub useragent: HeaderValue,
-	/// HTTP client
-	pub client: Client<HttpsConnector<HttpConnector>, Body>,
-	/// Tokio core
-	core: RefCell<Core>,
-	/// How to ratelimit (burst or steady)
-	pub limit: Cell<LimitMethod>,
-	/// Requests sent in the past ratelimit period
-	reqs: Cell<i32>,
-	/// Requests remaining
-	remaining: Cell<Option<i32>>,
-	/// Time when request amount will reset
-	reset_time: Cell<Instant>,
+	useragent: HeaderValue,
+	/// Base urls requests are sent to
+	urls: BaseUrls,
+	/// Transport that requests are sent with
+	transport: Box<Transport>,
+	/// Policy for retrying failed requests
+	retry: RetryPolicy,
+	/// Longest total wait before resubmitting a throttled submission, if they are resubmitted
+	throttle_wait: Mutex<Option<Duration>>,
+	/// Decides how long to wait before each request
+	limiter: RwLock<Arc<RateLimiter>>,
+	/// Ratelimiting info from the last response
+	status: Mutex<RatelimitStatus>,
+	/// Callback for when the ratelimit budget runs low
+	low_ratelimit: Option<LowRatelimit>,
+	/// Middleware requests and responses pass through
+	middleware: Vec<Box<Middleware>>,
+	/// Registry to record metrics in, if any
+	metrics: Option<Arc<Metrics>>,
+	/// Cache for responses to GET requests, if any
+	cache: Option<ResponseCache>,
+	/// Store to save authorization info in, if any
+	token_store: Option<Box<TokenStore>>,
+}
+
+struct LowRatelimit {
+	threshold: i32,
+	callback: Box<Fn(&RatelimitStatus) + Send + Sync>,
 }
 
 impl Connection {
@@ -63,181 +397,652 @@ impl Connection {
 	/// # Arguments
 	/// * `appname` - The name of the app
 	/// * `appversion` - The version of the app
-	/// * `appauthor` - The author of the app (should be in reddit form as /u/<username>)
+	/// * `appauthor` - The reddit username of the author of the app, with or without `/u/`
 	pub fn new(appname: &str, appversion: &str, appauthor: &str) -> Result<Connection, Error> {
-		let useragent = HeaderValue::from_str(&format!("linux:{}:{} (by {})", appname, appversion, appauthor)).unwrap();
-		let core = Core::new()?;
-		let client = Client::builder().build(HttpsConnector::new(1)?);
-		Ok(Connection {
-			auth: None,
-			useragent,
-			client,
-			core: RefCell::new(core),
-			limit: Cell::new(LimitMethod::Steady),
-			reqs: Cell::new(0),
-			remaining: Cell::new(None),
-			reset_time: Cell::new(Instant::now()),
-		})
+		ConnectionBuilder::new(appname, appversion, appauthor).build()
+	}
+
+	/// Returns the authorization info currently in use, if any
+	pub fn auth(&self) -> Option<OAuth> {
+		self.shared.auth.read().unwrap().clone()
+	}
+
+	/// Sets the authorization info to use for authorized requests. If the connection has a token
+	/// store, the new authorization info is saved in it, and failing to save it is logged.
+	pub fn set_auth(&self, auth: Option<OAuth>) {
+		if let (Some(ref store), Some(ref auth)) = (&self.shared.token_store, &auth) {
+			if let Err(e) = store.save(auth) {
+				warn!("Failed to save authorization info: {}", e);
+			}
+		}
+		*self.shared.auth.write().unwrap() = auth;
+	}
+
+	/// Returns the user agent sent with every request
+	pub fn useragent(&self) -> &HeaderValue {
+		&self.shared.useragent
+	}
+
+	/// Returns the base urls requests are sent to
+	pub fn urls(&self) -> &BaseUrls {
+		&self.shared.urls
+	}
+
+	/// Returns the transport requests are sent with
+	pub fn transport(&self) -> &Transport {
+		&*self.shared.transport
+	}
+
+	/// Returns a connection sharing everything with this one except its timeouts, for overriding
+	/// the timeouts of single calls. The connect timeout can't be overridden and is kept.
+	/// # Arguments
+	/// * `timeouts` - The timeouts for requests sent with the returned connection
+	pub fn with_timeouts(&self, timeouts: Timeouts) -> Connection {
+		Connection {
+			shared: Arc::clone(&self.shared),
+			timeouts: Timeouts { connect: self.timeouts.connect, ..timeouts },
+		}
+	}
+
+	/// Returns the timeouts of requests sent with this connection
+	pub fn timeouts(&self) -> Timeouts {
+		self.timeouts
+	}
+
+	/// Returns the policy for retrying failed requests
+	pub fn retry_policy(&self) -> &RetryPolicy {
+		&self.shared.retry
+	}
+
+	/// Returns the registry metrics are recorded in, if any
+	pub fn metrics(&self) -> Option<&Arc<Metrics>> {
+		self.shared.metrics.as_ref()
+	}
+
+	/// Returns the cache for responses to GET requests, if any
+	pub fn cache(&self) -> Option<&ResponseCache> {
+		self.shared.cache.as_ref()
+	}
+
+	/// Returns the store authorization info is saved in, if any
+	pub fn token_store(&self) -> Option<&TokenStore> {
+		self.shared.token_store.as_ref().map(|store| &**store)
+	}
+
+	/// Creates a url for a request that doesn't require authorization
+	/// # Arguments
+	/// * `path` - The path of the resource, starting with a slash
+	pub fn public_url(&self, path: &str) -> String {
+		format!("{}{}", self.shared.urls.public, path)
+	}
+
+	/// Creates a url for a request that requires OAuth authorization
+	/// # Arguments
+	/// * `path` - The path of the resource, starting with a slash
+	pub fn oauth_url(&self, path: &str) -> String {
+		format!("{}{}", self.shared.urls.oauth, path)
+	}
+
+	/// Creates a url for an access token request
+	/// # Arguments
+	/// * `path` - The path of the resource, starting with a slash
+	pub fn token_url(&self, path: &str) -> String {
+		format!("{}{}", self.shared.urls.token, path)
+	}
+
+	/// Creates a url for a request that only reads public data. If the connection is authorized,
+	/// including with application only authorization, the url is on the OAuth base url, as
+	/// authorized requests get a higher ratelimit, and otherwise on the public base url. Send the
+	/// request with `run_read_request`.
+	/// # Arguments
+	/// * `path` - The path of the resource, starting with a slash
+	pub fn read_url(&self, path: &str) -> String {
+		if self.auth().is_some() {
+			self.oauth_url(path)
+		} else {
+			self.public_url(path)
+		}
 	}
 
 	/// Send a request to reddit. This is where ratelimiting happens, as well as setting the
-	/// user agent.
-	pub fn run_request(&self, mut req: Request<Body>) -> Result<Value, Error> {
-		let req_str = format!("{:?}", req);
-
-		// Ratelimit based on method chosen type
-		match self.limit.get() {
-			LimitMethod::Steady => {
-				// Check if we have a remaining limit
-				if let Some(remaining) = self.remaining.get() {
-					// If the reset time is in the future
-					if Instant::now() < self.reset_time.get() {
-						trace!("Ratelimiting in steady mode for {:?}", self.reset_time.get() - Instant::now());
-						// Sleep for the amount of time until reset divided by how many requests we have for steady sending
-						thread::sleep((self.reset_time.get() - Instant::now()).checked_div(remaining as u32).unwrap());
-					}
-					// Else we must have already passed reset time and we will get a new one after this request
-				}
+	/// user agent. The ratelimiting wait is done by the returned future, so it doesn't block.
+	/// Requests that fail with a transient error are retried according to the retry policy, and
+	/// requests that take too long fail according to the timeouts of the connection. GET requests
+	/// are answered from the response cache if the connection has one and the endpoint is cached,
+	/// unless they are sent on behalf of a user.
+	pub fn run_request(&self, req: Request<Body>) -> RedditFuture<Value> {
+		let conn = self.clone();
+		let request = format!("{} {}", req.method(), req.uri());
+		let (mut parts, body) = req.into_parts();
+
+		// Responses to a user can differ between users, and the cache is keyed by url only
+		let for_user = parts.headers.contains_key(header::AUTHORIZATION) && self.auth().map_or(true, |auth| auth.is_user());
+		let ttl = match self.shared.cache {
+			Some(ref cache) if parts.method == Method::GET && !for_user => cache.ttl_for(&parts.uri),
+			_ => None,
+		};
+		let cached = match (ttl, &self.shared.cache) {
+			(Some(_), &Some(ref cache)) => cache.store().get(&parts.uri.to_string()),
+			_ => None,
+		};
+		match cached {
+			Some(ref cached) if cached.is_fresh() => {
+				trace!("Answering {} from the cache", request);
+				self.record_cache("hit");
+				return Box::new(future::result(json::from_str::<Value>(&cached.body).map_err(|_| Error::from(RedditError::BadResponse { request, response: cached.body.clone() }))));
 			}
-			LimitMethod::Burst => {
-				// Check if we have a remaining limit
-				if let Some(remaining) = self.remaining.get() {
-					// If we have none remaining and we haven't passed the request limit, sleep till we do
-					if remaining <= 0 && self.reset_time.get() > Instant::now() {
-						trace!("Ratelimiting in burst mode for {:?}", self.reset_time.get() - Instant::now());
-						thread::sleep(self.reset_time.get() - Instant::now());
+			Some(ref cached) if cached.is_revalidatable() => cache::add_conditional_headers(&mut parts.headers, cached),
+			_ => {}
+		}
+
+		// The body is buffered so the request can be sent again if it has to be retried
+		let response = body.concat2().from_err().and_then(move |body| {
+			let req = Arc::new(BufferedRequest {
+				method: parts.method,
+				uri: parts.uri,
+				version: parts.version,
+				headers: parts.headers,
+				body: body.to_vec(),
+			});
+
+			let response = {
+				let (conn, req) = (conn.clone(), Arc::clone(&req));
+				future::loop_fn(1, move |attempt| {
+					let (conn, req) = (conn.clone(), Arc::clone(&req));
+					conn.send_once(&req).then(move |result| -> RedditFuture<Loop<BufferedResponse, u32>> {
+						let delay = {
+							let response = result.as_ref().ok().map(|response| (response.status, &response.headers));
+							conn.shared.retry.retry_delay(&req.method, &req.uri, attempt, response)
+						};
+
+						if let Some(delay) = delay {
+							match result {
+								Ok(ref response) => warn!("Got {} from {} {}, retrying in {:?}", response.status, req.method, req.uri, delay),
+								Err(ref e) => warn!("Request {} {} failed ({}), retrying in {:?}", req.method, req.uri, e, delay),
+							}
+							if let Some(ref metrics) = conn.shared.metrics {
+								metrics.record_retry(&req.method, &req.uri);
+							}
+							return Box::new(Delay::new(Instant::now() + delay).from_err().map(move |_| Loop::Continue(attempt + 1)));
+						}
+
+						Box::new(future::result(result).map(Loop::Break))
+					})
+				})
+			};
+
+			response.and_then(move |mut response| {
+				let (ttl, cache) = match (ttl, &conn.shared.cache) {
+					(Some(ttl), &Some(ref cache)) => (ttl, cache),
+					_ => return response.into_value(&req),
+				};
+				let key = req.uri.to_string();
+
+				match cached {
+					// Not modified, so the cached response is still good
+					Some(mut cached) if response.status == StatusCode::NOT_MODIFIED => {
+						trace!("Cached response to {} {} was revalidated", req.method, req.uri);
+						conn.record_cache("revalidated");
+						response.status = StatusCode::OK;
+						response.body = cached.body.clone();
+						cached.expires = SystemTime::now() + ttl;
+						cache.store().put(&key, cached);
+						response.into_value(&req)
+					}
+					_ => {
+						conn.record_cache("miss");
+						let headers = response.headers.clone();
+						let body = response.body.clone();
+						let value = response.into_value(&req)?;
+						cache.store().put(&key, cache::cached_response(&headers, body, ttl));
+						Ok(value)
 					}
 				}
+			})
+		});
+
+		match self.timeouts.total {
+			Some(total) => Box::new(Timeout::new(response, total).map_err(move |e| timeout_error(e, request, TimeoutKind::Total, total))),
+			None => Box::new(response),
+		}
+	}
+
+	/// Sends the request described by an endpoint, with authorization headers if the endpoint
+	/// requires them, and parses its response
+	/// # Arguments
+	/// * `endpoint` - The endpoint to call
+	pub fn execute<E: Endpoint + Send + 'static>(&self, endpoint: E) -> RedditFuture<E::Response> {
+		let req = match endpoint::request(self, &endpoint) {
+			Ok(req) => req,
+			Err(e) => return Box::new(future::err(e)),
+		};
+		let response = if endpoint.requires_auth() { self.run_auth_request(req) } else { self.run_request(req) };
+
+		Box::new(response.and_then(move |response| endpoint.parse(response)))
+	}
+
+	/// Send a request with a url created by `read_url`. Requests to the OAuth base url are sent
+	/// with authorization headers like `run_auth_request`, others like `run_request`.
+	pub fn run_read_request(&self, req: Request<Body>) -> RedditFuture<Value> {
+		if self.auth().is_some() && req.uri().to_string().starts_with(&self.shared.urls.oauth) {
+			self.run_auth_request(req)
+		} else {
+			self.run_request(req)
+		}
+	}
+
+	/// Send a request to reddit with authorization headers. If the token has expired or is about
+	/// to expire and can be refreshed, it is refreshed before the request is sent. If reddit rejects
+	/// the token anyway, such as when it was revoked, the token is refreshed and the request is
+	/// sent once more.
+	pub fn run_auth_request(&self, req: Request<Body>) -> RedditFuture<Value> {
+		let auth = if let Some(auth) = self.auth() {
+			auth
+		} else {
+			return Box::new(future::err(Error::from(RedditError::Unauthorized { request: format!("{} {}", req.method(), req.uri()), status: 401 })));
+		};
+
+		let token: RedditFuture<String> = match auth.expire_instant() {
+			// If the token's about to expire, refresh it if we are able to
+			Some(_) if auth.needs_refresh() && auth.can_refresh() => self.refresh_auth(),
+			Some(expire_instant) if Instant::now() > expire_instant => {
+				return Box::new(future::err(Error::from(RedditError::Unauthorized { request: format!("{} {}", req.method(), req.uri()), status: 401 })));
 			}
+			_ => Box::new(future::ok(auth.token().to_string())),
 		};
 
-		// Set useragent
-		req.headers_mut().insert(header::USER_AGENT, self.useragent.clone());
+		// The request is buffered so it can be sent again with a new token
+		let conn = self.clone();
+		let can_refresh = auth.can_refresh();
+		let (parts, body) = req.into_parts();
+		Box::new(body.concat2().from_err().join(token).and_then(move |(body, token)| {
+			let req = BufferedRequest {
+				method: parts.method,
+				uri: parts.uri,
+				version: parts.version,
+				headers: parts.headers,
+				body: body.to_vec(),
+			};
 
-		// Log the request
-		trace!("Sending request {:?}", req);
+			conn.send_with_token(&req, &token).or_else(move |e| -> RedditFuture<Value> {
+				match e.downcast_ref::<RedditError>() {
+					Some(&RedditError::Unauthorized { status: 401, .. }) if can_refresh => {}
+					_ => return Box::new(future::err(e)),
+				}
 
-		// Execute the request!
-		let response = self.client.request(req);
-		let response = self.core.borrow_mut().run(response)?;
+				warn!("Token was rejected by {} {}, refreshing it and sending the request again", req.method, req.uri);
+				let retry_conn = conn.clone();
+				// Unless another request already replaced the rejected token
+				Box::new(conn.refresh_auth_unless(move |auth| auth.token() != token).and_then(move |token| retry_conn.send_with_token(&req, &token)))
+			})
+		}))
+	}
 
-		// Update values from response ratelimiting headers
-		if let Some(reqs_used) = response.headers().get("x-ratelimit-used") {
-			let reqs_used = reqs_used.to_str().unwrap().parse::<f32>().unwrap().round() as i32;
-			trace!("Used {} of requests in ratelimit period", reqs_used);
-			self.reqs.set(reqs_used);
-		}
-		if let Some(reqs_remaining) = response.headers().get("x-ratelimit-remaining") {
-			let reqs_remaining = reqs_remaining.to_str().unwrap().parse::<f32>().unwrap().round() as i32;
-			trace!("Have {} requests remaining in ratelimit period", reqs_remaining);
-			self.remaining.set(Some(reqs_remaining));
-		}
-		if let Some(secs_remaining) = response.headers().get("x-ratelimit-reset") {
-			let secs_remaining = secs_remaining.to_str().unwrap().parse::<f32>().unwrap().round() as u64;
-			trace!("Have {} seconds remaining to ratelimit reset", secs_remaining);
-			self.reset_time.set(Instant::now() + Duration::new(secs_remaining, 0));
-		}
-		trace!("Ratelimiting:\n\tRequests used: {:?}\n\tRequests remaining: {:?}\n\tReset time: {:?}\n\tNow: {:?}", self.reqs.get(), self.remaining.get(), self.reset_time.get(), Instant::now());
+	// Sends a buffered request with a bearer token
+	fn send_with_token(&self, req: &BufferedRequest, token: &str) -> RedditFuture<Value> {
+		let authorization = match HeaderValue::from_str(&format!("Bearer {}", token)) {
+			Ok(authorization) => authorization,
+			Err(_) => return Box::new(future::err(RedditError::AuthError.into())),
+		};
+		let mut req = req.to_request();
+		req.headers_mut().insert(header::AUTHORIZATION, authorization);
+		self.run_request(req)
+	}
+
+	/// Send a request that submits something, such as a comment, with authorization headers. If
+	/// reddit throttles the submission and waiting for throttled submissions is enabled (see
+	/// `set_throttle_wait`), the request is resubmitted once the wait reddit asked for has passed.
+	/// Otherwise the throttling results in a `RedditError::Ratelimited` error.
+	pub fn run_submit_request(&self, req: Request<Body>) -> RedditFuture<Value> {
+		let conn = self.clone();
+		let (parts, body) = req.into_parts();
+
+		Box::new(body.concat2().from_err().and_then(move |body| {
+			let req = Arc::new(BufferedRequest {
+				method: parts.method,
+				uri: parts.uri,
+				version: parts.version,
+				headers: parts.headers,
+				body: body.to_vec(),
+			});
+
+			future::loop_fn(Duration::from_secs(0), move |waited| {
+				let (conn, req) = (conn.clone(), Arc::clone(&req));
+				conn.run_auth_request(req.to_request()).then(move |result| -> RedditFuture<Loop<Value, Duration>> {
+					let wait = match result {
+						Err(ref e) => match (e.downcast_ref::<RedditError>(), conn.throttle_wait()) {
+							(Some(&RedditError::Ratelimited { wait: Some(wait), .. }), Some(max_wait)) if waited + wait <= max_wait => Some(wait),
+							_ => None,
+						},
+						Ok(_) => None,
+					};
+
+					match wait {
+						Some(wait) => {
+							warn!("Submission {} {} was throttled, resubmitting in {:?}", req.method, req.uri, wait);
+							Box::new(Delay::new(Instant::now() + wait).from_err().map(move |_| Loop::Continue(waited + wait)))
+						}
+						None => Box::new(future::result(result).map(Loop::Break)),
+					}
+				})
+			})
+		}))
+	}
+
+	/// Refreshes the token currently in use. If a refresh is already in progress, no new one is
+	/// started, and the future resolves to the token of the refresh in progress instead.
+	pub fn refresh_auth(&self) -> RedditFuture<String> {
+		// Another refresh may have finished since the caller found the token expired
+		self.refresh_auth_unless(|auth| auth.expire_instant().is_some() && !auth.needs_refresh())
+	}
+
+	// Refreshes the token currently in use, unless `fresh` returns true for the authorization info
+	// when the refresh would start
+	fn refresh_auth_unless<F: Fn(&OAuth) -> bool>(&self, fresh: F) -> RedditFuture<String> {
+		let mut refreshing = self.shared.refreshing.lock().unwrap();
+
+		let refresh = if let Some(ref refresh) = *refreshing {
+			trace!("Waiting for token refresh in progress");
+			refresh.clone()
+		} else {
+			let auth = match self.auth() {
+				Some(auth) => auth,
+				None => return Box::new(future::err(Error::from(RedditError::AuthError))),
+			};
+			if fresh(&auth) {
+				return Box::new(future::ok(auth.token().to_string()));
+			}
 
-		let response_str = format!("{:?}", response);
-		let get_body = |response: Response<Body>| -> Result<String, Error> {
-			let body = self.core.borrow_mut().run(response.into_body().concat2())?;
-			let body: String = String::from_utf8_lossy(&body).into();
-			Ok(body)
+			trace!("Refreshing token");
+			let (conn, err_conn) = (self.clone(), self.clone());
+			let refresh: Box<Future<Item = String, Error = String> + Send> = Box::new(
+				auth.refresh(self)
+					.map(move |auth| {
+						if let Some(ref metrics) = conn.shared.metrics {
+							metrics.record_refresh(true);
+						}
+						let token = auth.token().to_string();
+						// Set the new token before clearing the refresh so no request sees neither
+						conn.set_auth(Some(auth));
+						conn.shared.refreshing.lock().unwrap().take();
+						token
+					})
+					.map_err(move |e| {
+						if let Some(ref metrics) = err_conn.shared.metrics {
+							metrics.record_refresh(false);
+						}
+						err_conn.shared.refreshing.lock().unwrap().take();
+						e.to_string()
+					}),
+			);
+			let refresh = refresh.shared();
+			*refreshing = Some(refresh.clone());
+			refresh
 		};
 
-		if !response.status().is_success() {
-			error!("Got error response: {}", response_str);
-			return Err(Error::from(RedditError::BadRequest {
-				request: req_str,
-				response: format!("Reponse: {}\nResponse body: {:?}", response_str, get_body(response)?),
-			}));
+		Box::new(refresh.map(|token| (*token).clone()).map_err(|e| format_err!("Failed to refresh token: {}", *e)))
+	}
+
+	/// Sets the longest total wait before resubmitting a throttled submission, or None to return
+	/// an error for throttled submissions instead
+	pub fn set_throttle_wait(&self, max_wait: Option<Duration>) {
+		*self.shared.throttle_wait.lock().unwrap() = max_wait;
+	}
+
+	/// Returns the longest total wait before resubmitting a throttled submission, if they are
+	/// resubmitted
+	pub fn throttle_wait(&self) -> Option<Duration> {
+		*self.shared.throttle_wait.lock().unwrap()
+	}
+
+	/// Set's the ratelimiting method, replacing the rate limiter in use with a new one
+	pub fn set_limit(&self, limit: LimitMethod) {
+		match limit {
+			LimitMethod::Steady => self.set_ratelimiter(SteadyLimiter::new()),
+			LimitMethod::Burst => self.set_ratelimiter(BurstLimiter::new()),
 		}
+	}
 
-		let body = get_body(response)?;
+	/// Sets the rate limiter deciding how long to wait before each request. To share a limiter
+	/// between connections, pass the same `Arc` to each of them.
+	pub fn set_ratelimiter<R: RateLimiter + 'static>(&self, limiter: R) {
+		*self.shared.limiter.write().unwrap() = Arc::new(limiter);
+	}
 
-		match json::from_str(&body) {
-			Ok(r) => {
-				trace!("Got successful response: {:?}\nBody: {}", response_str, body);
-				Ok(r)
-			}
-			Err(_) => Err(Error::from(RedditError::BadResponse { request: req_str, response: body })),
+	/// Returns the ratelimit budget reported by the last response
+	pub fn ratelimit_status(&self) -> RatelimitStatus {
+		*self.shared.status.lock().unwrap()
+	}
+
+	/// Returns the rate limiter in use
+	pub fn ratelimiter(&self) -> Arc<RateLimiter> {
+		Arc::clone(&self.shared.limiter.read().unwrap())
+	}
+
+	// Records whether a request was answered from the cache
+	fn record_cache(&self, result: &'static str) {
+		if let Some(ref metrics) = self.shared.metrics {
+			metrics.record_cache(result);
 		}
 	}
 
-	/// Send a request to reddit with authorization headers
-	pub fn run_auth_request(&self, mut req: Request<Body>) -> Result<Value, Error> {
-		if let Some(ref auth) = self.auth {
-			let req_str = format!("{:?}", req);
-			req.headers_mut().insert(
-				header::AUTHORIZATION,
-				HeaderValue::from_str(&format!(
-					"Bearer {}",
-					match *auth {
-						OAuth::Script {
-							id: ref _id,
-							secret: ref _secret,
-							username: ref _username,
-							password: ref _password,
-							ref token,
-						} => token.to_string(),
-						OAuth::InstalledApp {
-							id: ref _id,
-							redirect: ref _redirect,
-							ref token,
-							ref refresh_token,
-							ref expire_instant,
-						} => {
-							// If the token can expire and we are able to refresh it
-							if let (Some(_refresh_token), Some(expire_instant)) = (refresh_token.borrow().clone(), expire_instant.get()) {
-								// If the token's expired, refresh it
-								if Instant::now() > expire_instant {
-									auth.refresh(self)?;
-								}
-								token.borrow().to_string()
-							} else if let Some(expire_instant) = expire_instant.get() {
-								if Instant::now() > expire_instant {
-									return Err(Error::from(RedditError::Forbidden { request: format!("{:?}", req_str) }));
-								} else {
-									token.borrow().to_string()
-								}
-							} else {
-								token.borrow().to_string()
-							}
+	// Sends a request once, after waiting for the ratelimit
+	fn send_once(&self, req: &BufferedRequest) -> RedditFuture<BufferedResponse> {
+		// Ratelimit based on method chosen type. The wait is calculated once the future is first polled.
+		let ratelimit = {
+			let conn = self.clone();
+			future::lazy(move || -> RedditFuture<()> {
+				match conn.ratelimiter().wait() {
+					Some(wait) => {
+						if let Some(ref metrics) = conn.shared.metrics {
+							metrics.record_ratelimit_sleep(wait);
 						}
+						Box::new(Delay::new(Instant::now() + wait).from_err())
 					}
-				))
-				.unwrap(),
-			);
-			self.run_request(req)
-		} else {
-			Err(Error::from(RedditError::Forbidden { request: format!("{:?}", req) }))
+					None => Box::new(future::ok(())),
+				}
+			})
+		};
+
+		let mut req = req.to_vec_request();
+		let conn = self.clone();
+		Box::new(ratelimit.and_then(move |_| {
+			// Set useragent
+			req.headers_mut().insert(header::USER_AGENT, conn.shared.useragent.clone());
+
+			for middleware in &conn.shared.middleware {
+				middleware.on_request(&mut req);
+			}
+
+			// Log the request
+			trace!("Sending request {:?}", req);
+
+			// Execute the request!
+			let request = format!("{} {}", req.method(), req.uri());
+			let (read, connect) = (conn.timeouts.read, conn.timeouts.connect);
+			let start = Instant::now();
+			let response = conn.shared.transport.send(body_request(&req));
+			let response: TransportFuture = match read {
+				// Connecting is part of sending, so the wait for the response includes it
+				Some(read) => {
+					let request = request.clone();
+					Box::new(Timeout::new(response, read + connect.unwrap_or_default()).map_err(move |e| timeout_error(e, request, TimeoutKind::Read, read)))
+				}
+				None => response,
+			};
+
+			response
+				.map_err({
+					let request = request.clone();
+					move |e| match connect {
+						Some(connect) if is_timed_out(&e) => Error::from(RedditError::Timeout { request, kind: TimeoutKind::Connect, after: connect }),
+						_ => e,
+					}
+				})
+				.and_then({
+					let conn = conn.clone();
+					move |response| conn.handle_response(response, request, start)
+				})
+				.then(move |result| {
+					if let Some(ref metrics) = conn.shared.metrics {
+						let status = result.as_ref().ok().map(|&(ref response, _)| response.status().as_u16());
+						metrics.record_request(req.method(), req.uri(), status, start.elapsed());
+					}
+					let result = result.map(|response| {
+						let (mut response, info) = response;
+						// Responses go through the middleware in reverse
+						for middleware in conn.shared.middleware.iter().rev() {
+							middleware.on_response(&req, &mut response, &info);
+						}
+						let (parts, body) = response.into_parts();
+						BufferedResponse {
+							status: parts.status,
+							headers: parts.headers,
+							body,
+						}
+					});
+					if let Err(ref e) = result {
+						for middleware in conn.shared.middleware.iter().rev() {
+							middleware.on_error(&req, e, start.elapsed());
+						}
+					}
+					result
+				})
+		}))
+	}
+
+	// Updates the ratelimiting state from the headers of a response and reads its body
+	fn handle_response(&self, response: Response<Body>, request: String, start: Instant) -> RedditFuture<(Response<String>, ResponseInfo)> {
+		// Update values from response ratelimiting headers
+		let headers = RatelimitHeaders::from_headers(response.headers());
+		self.ratelimiter().update(&headers);
+		if let Some(ref metrics) = self.shared.metrics {
+			metrics.record_ratelimit(headers.used, headers.remaining);
 		}
+		let dropped = {
+			let mut status = self.shared.status.lock().unwrap();
+			let before = status.remaining;
+			status.update(&headers);
+			match (&self.shared.low_ratelimit, before, status.remaining) {
+				(&Some(ref low), before, Some(after)) if after < low.threshold && before.map_or(true, |before| before >= low.threshold) => Some(*status),
+				_ => None,
+			}
+		};
+		if let (Some(status), Some(low)) = (dropped, self.shared.low_ratelimit.as_ref()) {
+			warn!("Ratelimit budget is running low: {:?}", status);
+			(low.callback)(&status);
+		}
+
+		let (parts, body) = response.into_parts();
+		let body: Box<Stream<Item = Chunk, Error = Error> + Send> = match self.timeouts.read {
+			Some(read) => Box::new(Timeout::new(body, read).map_err(move |e| timeout_error(e, request.clone(), TimeoutKind::Read, read))),
+			None => Box::new(body.from_err()),
+		};
+
+		Box::new(body.concat2().map(move |body| {
+			let info = ResponseInfo { elapsed: start.elapsed(), ratelimit: headers };
+			(Response::from_parts(parts, String::from_utf8_lossy(&body).into()), info)
+		}))
 	}
+}
 
-	/// Set's the ratelimiting method
-	pub fn set_limit(&self, limit: LimitMethod) {
-		self.limit.set(limit);
+// Copies a request with a buffered body into one that can be sent
+fn body_request(req: &Request<Vec<u8>>) -> Request<Body> {
+	let mut body_req = Request::new(Body::from(req.body().clone()));
+	*body_req.method_mut() = req.method().clone();
+	*body_req.uri_mut() = req.uri().clone();
+	*body_req.version_mut() = req.version();
+	*body_req.headers_mut() = req.headers().clone();
+	body_req
+}
+
+// Converts an error of a future with a timeout, turning an expired timeout into a RedditError
+fn timeout_error<E: Into<Error>>(e: TimeoutError<E>, request: String, kind: TimeoutKind, after: Duration) -> Error {
+	if e.is_elapsed() {
+		Error::from(RedditError::Timeout { request, kind, after })
+	} else if e.is_inner() {
+		e.into_inner().unwrap().into()
+	} else {
+		Error::from(e.into_timer().unwrap())
 	}
+}
 
-	/// Returns a reference to the tokio core in a RefCell
-	pub fn get_core(&self) -> &RefCell<Core> {
-		&self.core
+// Whether an error was caused by a connection attempt timing out
+fn is_timed_out(e: &Error) -> bool {
+	let mut cause: Option<&(StdError + 'static)> = match e.downcast_ref::<hyper::Error>() {
+		Some(e) => Some(e),
+		None => e.downcast_ref::<io::Error>().map(|e| e as &(StdError + 'static)),
+	};
+	while let Some(e) = cause {
+		if let Some(e) = e.downcast_ref::<io::Error>() {
+			return e.kind() == io::ErrorKind::TimedOut;
+		}
+		cause = e.source();
 	}
+	false
 }
 
-/// Creates a HTTP/hyper Body from a hashmap, in urlencoded form.
-pub fn body_from_map<S: BuildHasher>(map: &HashMap<&str, &str, S>) -> Body {
-	let mut body_str = String::new();
+// A request with its body read into memory, so it can be sent multiple times
+struct BufferedRequest {
+	method: Method,
+	uri: Uri,
+	version: Version,
+	headers: HeaderMap,
+	body: Vec<u8>,
+}
+
+impl BufferedRequest {
+	fn to_request(&self) -> Request<Body> {
+		body_request(&self.to_vec_request())
+	}
 
-	for (i, item) in map.iter().enumerate() {
-		// Push the paramater to the body with an & at the end unless it's the last parameter
-		body_str.push_str(&format!("{}={}{}", item.0, item.1, if i < map.len() - 1 { "&" } else { "" }));
+	fn to_vec_request(&self) -> Request<Vec<u8>> {
+		let mut req = Request::new(self.body.clone());
+		*req.method_mut() = self.method.clone();
+		*req.uri_mut() = self.uri.clone();
+		*req.version_mut() = self.version;
+		*req.headers_mut() = self.headers.clone();
+		req
 	}
+}
+
+// A response with its body read into memory
+struct BufferedResponse {
+	status: StatusCode,
+	headers: HeaderMap,
+	body: String,
+}
 
-	trace!("Setup body: \n{}\n", body_str);
+impl BufferedResponse {
+	// Parses the body of the response as json, or returns an error if the request failed
+	fn into_value(self, req: &BufferedRequest) -> Result<Value, Error> {
+		let req_str = format!("{} {}", req.method, req.uri);
 
-	Body::from(body_str)
+		if !self.status.is_success() {
+			error!("Got error response: {} {:?}\nBody: {}", self.status, self.headers, self.body);
+			let wait = retry::retry_after(&self.headers).or_else(|| retry::ratelimit_reset(&self.headers));
+			return Err(Error::from(RedditError::from_status(req_str, self.status, wait, self.body)));
+		}
+
+		match json::from_str::<Value>(&self.body) {
+			Ok(r) => {
+				// Some endpoints report failures in the body of a successful response
+				if let Some(e) = RedditError::from_json_errors(req_str, self.status, &r) {
+					error!("Got error in response: {} {:?}\nBody: {}", self.status, self.headers, self.body);
+					return Err(Error::from(e));
+				}
+				trace!("Got successful response: {} {:?}\nBody: {}", self.status, self.headers, self.body);
+				Ok(r)
+			}
+			Err(_) => Err(Error::from(RedditError::BadResponse { request: req_str, response: self.body })),
+		}
+	}
+}
+
+/// Creates a HTTP/hyper Body from a hashmap, in urlencoded form. Names and values are encoded, so
+/// they should be passed as raw strings. `form::Form` keeps the order of the parameters instead.
+pub fn body_from_map<S: BuildHasher>(map: &HashMap<&str, &str, S>) -> Body {
+	map.iter().fold(Form::new(), |form, (name, value)| form.param(name, value)).into()
 }
 
 /// Creates a url with encoded parameters from hashmap. Right now it's kinda hacky
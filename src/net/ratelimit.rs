@@ -0,0 +1,330 @@
+//! # Ratelimiting
+//! Reddit allows a limited amount of requests in every ratelimit period, and tells how much of
+//! that budget is left with the `x-ratelimit-used`, `x-ratelimit-remaining` and
+//! `x-ratelimit-reset` headers of each response. A `RateLimiter` decides how long a connection
+//! waits before sending each request, and is fed those headers after every response.
+//!
+//! `SteadyLimiter` and `BurstLimiter` implement the strategies of `LimitMethod`. The budget is
+//! counted per OAuth client, so when several connections use the same client they should share a
+//! single limiter, such as a `TokenBucketLimiter` wrapped in an `Arc`. Any other strategy can be
+//! used by implementing `RateLimiter`.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hyper::header::HeaderMap;
+
+/// The ratelimiting information sent by reddit with a response. Headers that weren't sent are
+/// None.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RatelimitHeaders {
+	/// Requests used in the current ratelimit period
+	pub used: Option<i32>,
+	/// Requests remaining in the current ratelimit period
+	pub remaining: Option<i32>,
+	/// Time until the current ratelimit period ends
+	pub reset: Option<Duration>,
+}
+
+impl RatelimitHeaders {
+	/// Reads the ratelimit headers of a response. Headers that can't be parsed are logged and
+	/// treated as if they weren't sent.
+	pub fn from_headers(headers: &HeaderMap) -> RatelimitHeaders {
+		RatelimitHeaders {
+			used: parse_header(headers, "x-ratelimit-used").map(|used| used.round() as i32),
+			remaining: parse_header(headers, "x-ratelimit-remaining").map(|remaining| remaining.round() as i32),
+			reset: parse_header(headers, "x-ratelimit-reset").map(|reset| Duration::from_secs(reset.max(0.0).round() as u64)),
+		}
+	}
+}
+
+// Parses a numeric header, logging and ignoring values that aren't numbers
+fn parse_header(headers: &HeaderMap, name: &str) -> Option<f32> {
+	let value = headers.get(name)?;
+	match value.to_str().ok().and_then(|value| value.trim().parse::<f32>().ok()) {
+		Some(number) if number.is_finite() => Some(number),
+		_ => {
+			warn!("Ignoring malformed {} header {:?}", name, value);
+			None
+		}
+	}
+}
+
+/// A snapshot of the ratelimit budget of a connection, as reported by the last response that
+/// included ratelimit headers
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RatelimitStatus {
+	/// Requests used in the current ratelimit period
+	pub used: Option<i32>,
+	/// Requests remaining in the current ratelimit period
+	pub remaining: Option<i32>,
+	/// When the current ratelimit period ends
+	pub reset: Option<Instant>,
+}
+
+impl RatelimitStatus {
+	/// Updates the status with the ratelimit headers of a response
+	pub fn update(&mut self, headers: &RatelimitHeaders) {
+		if headers.used.is_some() {
+			self.used = headers.used;
+		}
+		if headers.remaining.is_some() {
+			self.remaining = headers.remaining;
+		}
+		if let Some(reset) = headers.reset {
+			self.reset = Some(Instant::now() + reset);
+		}
+	}
+
+	/// Returns the time left until the current ratelimit period ends, if it is known
+	pub fn reset_in(&self) -> Option<Duration> {
+		let reset = self.reset?;
+		let now = Instant::now();
+		Some(if reset > now { reset - now } else { Duration::from_secs(0) })
+	}
+}
+
+/// Decides how long to wait before sending each request, based on the ratelimit headers of
+/// previous responses. A limiter can be shared between connections, which is why it has to keep
+/// its state behind a lock.
+pub trait RateLimiter: Send + Sync {
+	/// Returns how long to wait before sending the next request, if at all. This is called once
+	/// for every request, right before it is sent.
+	fn wait(&self) -> Option<Duration>;
+
+	/// Updates the limiter with the ratelimit headers of a response
+	/// # Arguments
+	/// * `headers` - The ratelimiting information of the response
+	fn update(&self, headers: &RatelimitHeaders);
+}
+
+impl<R: RateLimiter + ?Sized> RateLimiter for Arc<R> {
+	fn wait(&self) -> Option<Duration> {
+		(**self).wait()
+	}
+
+	fn update(&self, headers: &RatelimitHeaders) {
+		(**self).update(headers)
+	}
+}
+
+impl<R: RateLimiter + ?Sized> RateLimiter for Box<R> {
+	fn wait(&self) -> Option<Duration> {
+		(**self).wait()
+	}
+
+	fn update(&self, headers: &RatelimitHeaders) {
+		(**self).update(headers)
+	}
+}
+
+// The ratelimiting state reported by the last response
+#[derive(Debug)]
+struct Ratelimit {
+	/// Requests sent in the past ratelimit period
+	reqs: i32,
+	/// Requests remaining
+	remaining: Option<i32>,
+	/// Time when request amount will reset
+	reset_time: Instant,
+}
+
+impl Ratelimit {
+	fn new() -> Ratelimit {
+		Ratelimit {
+			reqs: 0,
+			remaining: None,
+			reset_time: Instant::now(),
+		}
+	}
+
+	fn update(&mut self, headers: &RatelimitHeaders) {
+		if let Some(reqs_used) = headers.used {
+			trace!("Used {} of requests in ratelimit period", reqs_used);
+			self.reqs = reqs_used;
+		}
+		if let Some(reqs_remaining) = headers.remaining {
+			trace!("Have {} requests remaining in ratelimit period", reqs_remaining);
+			self.remaining = Some(reqs_remaining);
+		}
+		if let Some(reset) = headers.reset {
+			trace!("Have {:?} remaining to ratelimit reset", reset);
+			self.reset_time = Instant::now() + reset;
+		}
+		trace!("Ratelimiting:\n\tRequests used: {:?}\n\tRequests remaining: {:?}\n\tReset time: {:?}\n\tNow: {:?}", self.reqs, self.remaining, self.reset_time, Instant::now());
+	}
+}
+
+/// Waits an even amount of time between each request, spreading the remaining requests over the
+/// rest of the ratelimit period
+#[derive(Debug)]
+pub struct SteadyLimiter {
+	ratelimit: Mutex<Ratelimit>,
+}
+
+impl SteadyLimiter {
+	/// Creates a limiter that hasn't seen any ratelimit headers yet
+	pub fn new() -> SteadyLimiter {
+		SteadyLimiter { ratelimit: Mutex::new(Ratelimit::new()) }
+	}
+}
+
+impl Default for SteadyLimiter {
+	fn default() -> Self {
+		SteadyLimiter::new()
+	}
+}
+
+impl RateLimiter for SteadyLimiter {
+	fn wait(&self) -> Option<Duration> {
+		let ratelimit = self.ratelimit.lock().unwrap();
+		// Check if we have a remaining limit
+		if let Some(remaining) = ratelimit.remaining {
+			// If the reset time is in the future
+			if Instant::now() < ratelimit.reset_time {
+				let until_reset = ratelimit.reset_time - Instant::now();
+				trace!("Ratelimiting in steady mode for {:?}", until_reset);
+				// Without any requests left, wait for the reset
+				if remaining <= 0 {
+					return Some(until_reset);
+				}
+				// Wait for the amount of time until reset divided by how many requests we have for steady sending
+				return until_reset.checked_div(remaining as u32);
+			}
+			// Else we must have already passed reset time and we will get a new one after this request
+		}
+		None
+	}
+
+	fn update(&self, headers: &RatelimitHeaders) {
+		self.ratelimit.lock().unwrap().update(headers);
+	}
+}
+
+/// Fires off requests as they come, and only waits once the budget of the ratelimit period has
+/// been used up
+#[derive(Debug)]
+pub struct BurstLimiter {
+	ratelimit: Mutex<Ratelimit>,
+}
+
+impl BurstLimiter {
+	/// Creates a limiter that hasn't seen any ratelimit headers yet
+	pub fn new() -> BurstLimiter {
+		BurstLimiter { ratelimit: Mutex::new(Ratelimit::new()) }
+	}
+}
+
+impl Default for BurstLimiter {
+	fn default() -> Self {
+		BurstLimiter::new()
+	}
+}
+
+impl RateLimiter for BurstLimiter {
+	fn wait(&self) -> Option<Duration> {
+		let ratelimit = self.ratelimit.lock().unwrap();
+		// Check if we have a remaining limit
+		if let Some(remaining) = ratelimit.remaining {
+			// If we have none remaining and we haven't passed the request limit, wait till we do
+			if remaining <= 0 && ratelimit.reset_time > Instant::now() {
+				trace!("Ratelimiting in burst mode for {:?}", ratelimit.reset_time - Instant::now());
+				return Some(ratelimit.reset_time - Instant::now());
+			}
+		}
+		None
+	}
+
+	fn update(&self, headers: &RatelimitHeaders) {
+		self.ratelimit.lock().unwrap().update(headers);
+	}
+}
+
+/// A token bucket that holds up to `capacity` requests and refills at a constant rate. Each request
+/// takes a token, and waits for one to be refilled if the bucket is empty. Because the bucket
+/// reserves a token for every request it is asked about, it can be shared between connections by
+/// wrapping it in an `Arc`, and the connections together will never exceed its rate.
+///
+/// The bucket also never holds more tokens than reddit reports as remaining, so it adapts when the
+/// same OAuth client is used elsewhere.
+#[derive(Debug)]
+pub struct TokenBucketLimiter {
+	capacity: f64,
+	// Tokens refilled per second
+	rate: f64,
+	bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+	// Can be negative when requests have reserved tokens that haven't been refilled yet
+	tokens: f64,
+	last_refill: Instant,
+}
+
+impl TokenBucketLimiter {
+	/// Creates a full bucket
+	/// # Arguments
+	/// * `capacity` - The most requests that can be sent at once
+	/// * `period` - How long it takes for an empty bucket to fill up again
+	/// # Panics
+	/// If `capacity` or `period` is zero, since the bucket would never refill
+	pub fn new(capacity: u32, period: Duration) -> TokenBucketLimiter {
+		assert!(capacity > 0, "TokenBucketLimiter capacity must be greater than zero");
+		assert!(period > Duration::from_secs(0), "TokenBucketLimiter period must be greater than zero");
+		let period = period.as_secs() as f64 + f64::from(period.subsec_nanos()) / 1_000_000_000.0;
+		TokenBucketLimiter {
+			capacity: f64::from(capacity),
+			rate: f64::from(capacity) / period,
+			bucket: Mutex::new(Bucket {
+				tokens: f64::from(capacity),
+				last_refill: Instant::now(),
+			}),
+		}
+	}
+
+	fn refill(&self, bucket: &mut Bucket) {
+		let now = Instant::now();
+		let elapsed = now - bucket.last_refill;
+		let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1_000_000_000.0;
+		bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.capacity);
+		bucket.last_refill = now;
+	}
+}
+
+impl Default for TokenBucketLimiter {
+	/// A bucket matching reddit's OAuth ratelimit of 600 requests every 10 minutes
+	fn default() -> Self {
+		TokenBucketLimiter::new(600, Duration::from_secs(600))
+	}
+}
+
+impl RateLimiter for TokenBucketLimiter {
+	fn wait(&self) -> Option<Duration> {
+		let mut bucket = self.bucket.lock().unwrap();
+		self.refill(&mut bucket);
+
+		// Take a token, waiting for it to be refilled if there is none
+		bucket.tokens -= 1.0;
+		if bucket.tokens >= 0.0 {
+			None
+		} else {
+			let wait = -bucket.tokens / self.rate;
+			trace!("Ratelimiting with token bucket for {}s", wait);
+			Some(Duration::from_millis((wait * 1000.0).ceil() as u64))
+		}
+	}
+
+	fn update(&self, headers: &RatelimitHeaders) {
+		if let Some(remaining) = headers.remaining {
+			let mut bucket = self.bucket.lock().unwrap();
+			self.refill(&mut bucket);
+			bucket.tokens = bucket.tokens.min(f64::from(remaining));
+			// With nothing remaining, the next token comes when the ratelimit period resets
+			if let (true, Some(reset)) = (remaining <= 0, headers.reset) {
+				let reset = reset.as_secs() as f64 + f64::from(reset.subsec_nanos()) / 1_000_000_000.0;
+				bucket.tokens = bucket.tokens.min(1.0 - reset * self.rate);
+			}
+		}
+	}
+}
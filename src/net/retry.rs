@@ -0,0 +1,193 @@
+//! # Retries
+//! Reddit regularly answers requests with transient errors such as 503 Service Unavailable, or
+//! drops connections. A `RetryPolicy` describes which of these failures a `Connection` should retry
+//! on its own, how many times, and how long it should wait in between.
+//!
+//! Only requests that are safe to send more than once are retried. By default these are requests
+//! with an idempotent method and a few POST endpoints that don't change anything, such as loading
+//! more comments. Other POST requests, such as commenting, can be opted in with `retry_path` or
+//! `retry_method`, at the risk of submitting something twice.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hyper::header::{self, HeaderMap};
+use hyper::{Method, StatusCode, Uri};
+use rand::{self, Rng};
+
+/// Describes which failed requests a connection retries and how long it waits before doing so.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+	/// The maximum amount of attempts for a single request, including the first one
+	pub max_attempts: u32,
+	/// How long to wait before the first retry
+	pub base_delay: Duration,
+	/// The factor the wait grows by with each retry
+	pub multiplier: f64,
+	/// The longest time to wait between retries (unless reddit asks for a longer one)
+	pub max_delay: Duration,
+	/// Fraction of each wait that is randomized, between 0 and 1. With a jitter of 0.5, each wait
+	/// is somewhere between half of and the full backoff time.
+	pub jitter: f64,
+	/// Response statuses that are retried
+	pub statuses: Vec<StatusCode>,
+	/// Request methods that are retried
+	pub methods: Vec<Method>,
+	/// Paths of requests that are retried regardless of their method. A path matches if the path
+	/// of the request starts with it.
+	pub paths: Vec<String>,
+	/// Whether to retry requests that failed before a response was recieved, like when the
+	/// connection is reset
+	pub connection_errors: bool,
+}
+
+impl RetryPolicy {
+	/// Creates a policy that never retries
+	pub fn none() -> RetryPolicy {
+		RetryPolicy { max_attempts: 1, ..RetryPolicy::default() }
+	}
+
+	/// Sets the maximum amount of attempts for a single request, including the first one
+	pub fn max_attempts(mut self, max_attempts: u32) -> RetryPolicy {
+		self.max_attempts = max_attempts;
+		self
+	}
+
+	/// Sets the backoff curve
+	/// # Arguments
+	/// * `base_delay` - How long to wait before the first retry
+	/// * `multiplier` - The factor the wait grows by with each retry
+	/// * `max_delay` - The longest time to wait between retries
+	pub fn backoff(mut self, base_delay: Duration, multiplier: f64, max_delay: Duration) -> RetryPolicy {
+		self.base_delay = base_delay;
+		self.multiplier = multiplier;
+		self.max_delay = max_delay;
+		self
+	}
+
+	/// Sets the fraction of each wait that is randomized, between 0 and 1
+	pub fn jitter(mut self, jitter: f64) -> RetryPolicy {
+		self.jitter = jitter.max(0.0).min(1.0);
+		self
+	}
+
+	/// Adds a response status to retry
+	pub fn retry_status(mut self, status: StatusCode) -> RetryPolicy {
+		self.statuses.push(status);
+		self
+	}
+
+	/// Adds a request method to retry. Adding `POST` makes every request retryable, including
+	/// ones that aren't idempotent.
+	pub fn retry_method(mut self, method: Method) -> RetryPolicy {
+		self.methods.push(method);
+		self
+	}
+
+	/// Adds a path to retry regardless of the method of the request, such as `/api/comment`
+	pub fn retry_path(mut self, path: &str) -> RetryPolicy {
+		self.paths.push(path.to_string());
+		self
+	}
+
+	/// Sets whether to retry requests that failed before a response was recieved
+	pub fn retry_connection_errors(mut self, retry: bool) -> RetryPolicy {
+		self.connection_errors = retry;
+		self
+	}
+
+	/// Whether a request can be retried at all by this policy
+	pub fn is_retryable(&self, method: &Method, uri: &Uri) -> bool {
+		self.methods.contains(method) || self.paths.iter().any(|path| uri.path().starts_with(path.as_str()))
+	}
+
+	/// Returns how long to wait before retrying a request, or None if it shouldn't be retried.
+	/// # Arguments
+	/// * `method` - Method of the request
+	/// * `uri` - Uri of the request
+	/// * `attempt` - The attempt that just failed, starting at 1
+	/// * `response` - The status and headers of the response, or None if no response was recieved
+	pub fn retry_delay(&self, method: &Method, uri: &Uri, attempt: u32, response: Option<(StatusCode, &HeaderMap)>) -> Option<Duration> {
+		if attempt >= self.max_attempts || !self.is_retryable(method, uri) {
+			return None;
+		}
+
+		match response {
+			Some((status, headers)) => {
+				if !self.statuses.contains(&status) {
+					return None;
+				}
+				// Reddit knows best how long to wait
+				if let Some(retry_after) = retry_after(headers) {
+					return Some(retry_after);
+				}
+				let backoff = self.backoff_delay(attempt);
+				// If the ratelimit has been used up, there's no point in retrying before it resets
+				match ratelimit_reset(headers) {
+					Some(reset) if reset > backoff => Some(reset),
+					_ => Some(backoff),
+				}
+			}
+			None if self.connection_errors => Some(self.backoff_delay(attempt)),
+			None => None,
+		}
+	}
+
+	/// Returns the backoff time after an attempt, starting at 1
+	pub fn backoff_delay(&self, attempt: u32) -> Duration {
+		let base = duration_secs(self.base_delay) * self.multiplier.powi(attempt as i32 - 1);
+		let delay = base.min(duration_secs(self.max_delay));
+		let delay = if self.jitter > 0.0 { delay * (1.0 - self.jitter * rand::thread_rng().gen_range(0.0, 1.0)) } else { delay };
+
+		Duration::from_millis((delay * 1000.0) as u64)
+	}
+}
+
+impl Default for RetryPolicy {
+	/// Retries up to two times on 429, 500, 502, 503 and 504 responses and connection errors, with
+	/// waits starting at one second and doubling each time
+	fn default() -> Self {
+		RetryPolicy {
+			max_attempts: 3,
+			base_delay: Duration::from_secs(1),
+			multiplier: 2.0,
+			max_delay: Duration::from_secs(60),
+			jitter: 0.5,
+			statuses: vec![
+				StatusCode::TOO_MANY_REQUESTS,
+				StatusCode::INTERNAL_SERVER_ERROR,
+				StatusCode::BAD_GATEWAY,
+				StatusCode::SERVICE_UNAVAILABLE,
+				StatusCode::GATEWAY_TIMEOUT,
+			],
+			methods: vec![Method::GET, Method::HEAD, Method::OPTIONS, Method::PUT, Method::DELETE],
+			paths: vec!["/api/morechildren".to_string(), "/api/v1/access_token".to_string()],
+			connection_errors: true,
+		}
+	}
+}
+
+fn duration_secs(duration: Duration) -> f64 {
+	duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0
+}
+
+// Parses the Retry-After header, which reddit sends as a number of seconds. It can also be an
+// HTTP date, which is waited for unless it has already passed.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+	let value = headers.get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+	if let Ok(seconds) = value.parse::<u64>() {
+		return Some(Duration::from_secs(seconds));
+	}
+
+	let date = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+	Some(date.signed_duration_since(Utc::now()).to_std().unwrap_or_default())
+}
+
+// Returns the time until the ratelimit resets if no requests are remaining
+pub(crate) fn ratelimit_reset(headers: &HeaderMap) -> Option<Duration> {
+	let parse = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).and_then(|value| value.parse::<f32>().ok());
+	match (parse("x-ratelimit-remaining"), parse("x-ratelimit-reset")) {
+		(Some(remaining), Some(reset)) if remaining < 1.0 && reset >= 0.0 => Some(Duration::from_secs(reset.round() as u64)),
+		_ => None,
+	}
+}
@@ -0,0 +1,96 @@
+//! # Timeouts and cancellation
+//! `Timeouts` limit how long a request may take, so a stalled connection to reddit fails with
+//! `RedditError::Timeout` instead of hanging forever. They are set for a whole connection with
+//! `ConnectionBuilder::timeouts`, and can be overridden for single calls with
+//! `Connection::with_timeouts` or `AsyncApp::with_timeouts`.
+//!
+//! Futures returned by orca can be cancelled by dropping them, which also drops the request in
+//! flight. When the code that wants to cancel a request isn't the code that owns its future,
+//! `cancellable` makes a future that can be cancelled from elsewhere with a `CancelHandle`.
+
+use std::time::Duration;
+
+use failure::Error;
+use futures::future;
+use futures::sync::oneshot;
+use futures::Future;
+
+use errors::RedditError;
+use net::RedditFuture;
+
+/// How long a request may take
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timeouts {
+	/// How long to wait for a connection to reddit (or the proxy) to be established. Connections
+	/// are pooled and shared between requests, so this can only be set for a whole connection, and
+	/// is ignored when overriding the timeouts of a single call.
+	pub connect: Option<Duration>,
+	/// How long to wait for the response to start after the request was sent, and for each part
+	/// of its body after that
+	pub read: Option<Duration>,
+	/// How long the whole request may take, including ratelimiting waits and retries
+	pub total: Option<Duration>,
+}
+
+impl Timeouts {
+	/// Timeouts that never expire
+	pub fn none() -> Timeouts {
+		Timeouts { connect: None, read: None, total: None }
+	}
+
+	/// Sets the connect timeout
+	pub fn connect(mut self, timeout: Duration) -> Timeouts {
+		self.connect = Some(timeout);
+		self
+	}
+
+	/// Sets the read timeout
+	pub fn read(mut self, timeout: Duration) -> Timeouts {
+		self.read = Some(timeout);
+		self
+	}
+
+	/// Sets the total timeout
+	pub fn total(mut self, timeout: Duration) -> Timeouts {
+		self.total = Some(timeout);
+		self
+	}
+}
+
+impl Default for Timeouts {
+	/// Waits 30 seconds for connecting and 60 seconds for reading, without limiting the total time
+	fn default() -> Self {
+		Timeouts::none().connect(Duration::from_secs(30)).read(Duration::from_secs(60))
+	}
+}
+
+/// Cancels the future it was created with by `cancellable`
+#[derive(Debug)]
+pub struct CancelHandle {
+	sender: oneshot::Sender<()>,
+}
+
+impl CancelHandle {
+	/// Cancels the future, making it fail with `RedditError::Cancelled`. The request it was
+	/// running, if any, is dropped. Does nothing if the future has already finished.
+	pub fn cancel(self) {
+		let _ = self.sender.send(());
+	}
+}
+
+/// Makes a future that can be cancelled with the returned handle. Dropping the handle without
+/// calling `cancel` doesn't cancel the future.
+/// # Arguments
+/// * `future` - The future to make cancellable, such as one returned by an `AsyncApp` method
+pub fn cancellable<T: Send + 'static>(future: RedditFuture<T>) -> (RedditFuture<T>, CancelHandle) {
+	let (sender, receiver) = oneshot::channel();
+	let cancelled = receiver.then(|result| -> RedditFuture<T> {
+		match result {
+			Ok(()) => Box::new(future::err(Error::from(RedditError::Cancelled))),
+			// The handle was dropped without cancelling, so the future just runs to completion
+			Err(_) => Box::new(future::empty()),
+		}
+	});
+
+	(Box::new(future.select(cancelled).map(|(item, _)| item).map_err(|(e, _)| e)), CancelHandle { sender })
+}
@@ -0,0 +1,115 @@
+//! # Token stores
+//! Installed apps get a refresh token when the user authorizes them, which can be used to get new
+//! tokens without asking the user again. A `TokenStore` keeps the authorization info of a
+//! connection between runs of a program: the connection saves it whenever it changes, such as when
+//! a token is refreshed or reddit rotates the refresh token, and `AsyncApp::restore_auth` loads it
+//! when the program starts.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use failure::Error;
+use json;
+
+use net::auth::OAuth;
+
+/// Somewhere to keep authorization info between runs of a program. A store can be shared between
+/// connections, which is why it has to keep its state behind a lock.
+pub trait TokenStore: Send + Sync {
+	/// Returns the authorization info saved last, if any
+	fn load(&self) -> Result<Option<OAuth>, Error>;
+
+	/// Saves authorization info, replacing the info saved before
+	fn save(&self, auth: &OAuth) -> Result<(), Error>;
+}
+
+impl<S: TokenStore + ?Sized> TokenStore for Arc<S> {
+	fn load(&self) -> Result<Option<OAuth>, Error> {
+		(**self).load()
+	}
+
+	fn save(&self, auth: &OAuth) -> Result<(), Error> {
+		(**self).save(auth)
+	}
+}
+
+impl<S: TokenStore + ?Sized> TokenStore for Box<S> {
+	fn load(&self) -> Result<Option<OAuth>, Error> {
+		(**self).load()
+	}
+
+	fn save(&self, auth: &OAuth) -> Result<(), Error> {
+		(**self).save(auth)
+	}
+}
+
+/// A token store keeping authorization info in a json file. The file contains the secrets of the
+/// app and the tokens of the user, so it is created readable only by its owner where the platform
+/// supports it.
+#[derive(Debug)]
+pub struct FileTokenStore {
+	path: PathBuf,
+	lock: Mutex<()>,
+}
+
+impl FileTokenStore {
+	/// Creates a store for a file, which doesn't have to exist yet
+	/// # Arguments
+	/// * `path` - The path of the file
+	pub fn new<P: AsRef<Path>>(path: P) -> FileTokenStore {
+		FileTokenStore { path: path.as_ref().to_path_buf(), lock: Mutex::new(()) }
+	}
+
+	/// Returns the path of the file
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl TokenStore for FileTokenStore {
+	fn load(&self) -> Result<Option<OAuth>, Error> {
+		let _lock = self.lock.lock().unwrap();
+
+		let mut contents = String::new();
+		match File::open(&self.path) {
+			Ok(mut file) => file.read_to_string(&mut contents)?,
+			Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+			Err(e) => return Err(e.into()),
+		};
+
+		Ok(Some(json::from_str(&contents)?))
+	}
+
+	fn save(&self, auth: &OAuth) -> Result<(), Error> {
+		let _lock = self.lock.lock().unwrap();
+
+		// Write to a temporary file first so a crash can't leave a half written file behind. A
+		// temporary file left behind before is removed, since it may be readable by others.
+		let mut tmp = self.path.clone().into_os_string();
+		tmp.push(".tmp");
+		let tmp = PathBuf::from(tmp);
+		match fs::remove_file(&tmp) {
+			Ok(()) => {}
+			Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+			Err(e) => return Err(e.into()),
+		}
+		create_private(&tmp)?.write_all(json::to_string_pretty(auth)?.as_bytes())?;
+		fs::rename(&tmp, &self.path)?;
+
+		Ok(())
+	}
+}
+
+#[cfg(unix)]
+fn create_private(path: &Path) -> io::Result<File> {
+	use std::os::unix::fs::OpenOptionsExt;
+
+	fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> io::Result<File> {
+	File::create(path)
+}
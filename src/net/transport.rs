@@ -0,0 +1,354 @@
+//! # Transports
+//! A transport is what actually sends a request over the network (or doesn't). Every request sent
+//! by a `Connection` goes through its transport after ratelimiting and authorization have been
+//! handled, so swapping the transport changes how requests are delivered without changing anything
+//! else about the connection.
+//!
+//! By default a connection uses a `HyperTransport`, which sends requests to reddit with a hyper
+//! client. A `MockTransport` is also provided, which never touches the network and instead answers
+//! requests with canned responses. This is useful for testing code that uses orca offline.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use failure::Error;
+use futures::{future, Future, Stream};
+use hyper::client::connect::{Connect, Connected, Destination};
+use hyper::client::{Client, HttpConnector};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Body, Method, Request, Response, StatusCode, Uri};
+use hyper_tls::{HttpsConnector, MaybeHttpsStream};
+use json::{self, Value};
+use native_tls::TlsConnector;
+use tokio::net::TcpStream;
+use tokio::timer::Delay;
+
+use net::proxy::{Proxy, ProxyConnector};
+
+/// The future returned by a transport, resolving to the response to a request
+pub type TransportFuture = Box<Future<Item = Response<Body>, Error = Error> + Send>;
+
+/// Something that can send a request and retrieve the response
+pub trait Transport: Send + Sync {
+	/// Sends a request, returning a future that resolves to the response
+	/// # Arguments
+	/// * `req` - The request to send. It will already have all headers set by the connection.
+	fn send(&self, req: Request<Body>) -> TransportFuture;
+}
+
+/// The HTTPS client of a `HyperTransport`
+pub type HttpsClient = Client<AlpnConnector<HttpsConnector<ProxyConnector>>, Body>;
+
+/// A transport that sends requests over the network with a hyper client. This is the default
+/// transport of a connection.
+///
+/// The client keeps a pool of open connections, so requests made one after another reuse the same
+/// connection instead of opening a new one each time, as long as keep-alive is enabled.
+pub struct HyperTransport {
+	/// HTTP client
+	pub client: HttpsClient,
+}
+
+impl HyperTransport {
+	/// Creates a new transport with a default HTTPS client
+	pub fn new() -> Result<HyperTransport, Error> {
+		HyperTransport::with_proxy(None)
+	}
+
+	/// Creates a new transport with an HTTPS client that connects through a proxy
+	/// # Arguments
+	/// * `proxy` - The proxy to connect through, or None to connect directly
+	pub fn with_proxy(proxy: Option<Proxy>) -> Result<HyperTransport, Error> {
+		let mut builder = HyperTransport::builder();
+		builder.proxy = proxy;
+		builder.build()
+	}
+
+	/// Creates a builder for configuring the HTTPS client of a transport
+	pub fn builder() -> HyperTransportBuilder {
+		HyperTransportBuilder::default()
+	}
+}
+
+/// A builder for a `HyperTransport`
+#[derive(Debug, Clone)]
+pub struct HyperTransportBuilder {
+	/// The proxy to connect through, or None to connect directly
+	pub proxy: Option<Proxy>,
+	/// How long to wait for a connection to be established
+	pub connect_timeout: Option<Duration>,
+	/// Whether connections are kept open after a request to be reused by later ones
+	pub keep_alive: bool,
+	/// The most idle connections to keep open to a single host
+	pub pool_max_idle: usize,
+	/// How long an idle connection is kept open, or None to keep it open until the server closes it
+	pub pool_idle_timeout: Option<Duration>,
+	/// Whether to use HTTP/2 with servers that support it, which sends all requests over a single
+	/// connection. The protocol is negotiated with ALPN, so servers without HTTP/2 support still
+	/// get HTTP/1.1.
+	pub http2: bool,
+	/// The amount of threads resolving host names
+	pub dns_threads: usize,
+}
+
+impl HyperTransportBuilder {
+	/// Sets the proxy to connect through
+	pub fn proxy(mut self, proxy: Proxy) -> HyperTransportBuilder {
+		self.proxy = Some(proxy);
+		self
+	}
+
+	/// Sets how long to wait for a connection to be established
+	pub fn connect_timeout(mut self, timeout: Duration) -> HyperTransportBuilder {
+		self.connect_timeout = Some(timeout);
+		self
+	}
+
+	/// Sets whether connections are kept open to be reused
+	pub fn keep_alive(mut self, keep_alive: bool) -> HyperTransportBuilder {
+		self.keep_alive = keep_alive;
+		self
+	}
+
+	/// Sets the most idle connections to keep open to a single host
+	pub fn pool_max_idle(mut self, max_idle: usize) -> HyperTransportBuilder {
+		self.pool_max_idle = max_idle;
+		self
+	}
+
+	/// Sets how long an idle connection is kept open, or None to keep it open indefinitely
+	pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> HyperTransportBuilder {
+		self.pool_idle_timeout = timeout;
+		self
+	}
+
+	/// Sets whether to use HTTP/2 with servers that support it
+	pub fn http2(mut self, http2: bool) -> HyperTransportBuilder {
+		self.http2 = http2;
+		self
+	}
+
+	/// Sets the amount of threads resolving host names
+	pub fn dns_threads(mut self, threads: usize) -> HyperTransportBuilder {
+		self.dns_threads = threads;
+		self
+	}
+
+	/// Creates the transport
+	pub fn build(self) -> Result<HyperTransport, Error> {
+		let connector = ProxyConnector::from_http(self.proxy, HttpConnector::new(self.dns_threads.max(1))).connect_timeout(self.connect_timeout);
+		let mut tls = TlsConnector::builder();
+		if self.http2 {
+			tls.request_alpns(&["h2", "http/1.1"]);
+		}
+		let connector = AlpnConnector { inner: HttpsConnector::from((connector, tls.build()?)) };
+
+		let client = Client::builder().keep_alive(self.keep_alive).max_idle_per_host(self.pool_max_idle).keep_alive_timeout(self.pool_idle_timeout).build(connector);
+		Ok(HyperTransport { client })
+	}
+}
+
+impl Default for HyperTransportBuilder {
+	/// Connects directly over HTTP/1.1 with keep-alive, keeping any amount of idle connections open
+	/// for 90 seconds, and resolves host names with 4 threads
+	fn default() -> Self {
+		HyperTransportBuilder {
+			proxy: None,
+			connect_timeout: None,
+			keep_alive: true,
+			pool_max_idle: usize::max_value(),
+			pool_idle_timeout: Some(Duration::from_secs(90)),
+			http2: false,
+			dns_threads: 4,
+		}
+	}
+}
+
+/// A connector that tells hyper to use HTTP/2 on TLS connections that negotiated it with ALPN
+#[derive(Clone)]
+pub struct AlpnConnector<C> {
+	inner: C,
+}
+
+impl<C> Connect for AlpnConnector<C>
+where
+	C: Connect<Transport = MaybeHttpsStream<TcpStream>>,
+	C::Future: 'static,
+{
+	type Transport = MaybeHttpsStream<TcpStream>;
+	type Error = C::Error;
+	type Future = Box<Future<Item = (Self::Transport, Connected), Error = C::Error> + Send>;
+
+	fn connect(&self, dst: Destination) -> Self::Future {
+		Box::new(self.inner.connect(dst).map(|(stream, connected)| {
+			let h2 = match stream {
+				MaybeHttpsStream::Https(ref tls) => tls.get_ref().negotiated_alpn().ok().and_then(|alpn| alpn).map_or(false, |alpn| alpn == b"h2"),
+				MaybeHttpsStream::Http(_) => false,
+			};
+			(stream, if h2 { connected.negotiated_h2() } else { connected })
+		}))
+	}
+}
+
+impl Transport for HyperTransport {
+	fn send(&self, req: Request<Body>) -> TransportFuture {
+		Box::new(self.client.request(req).from_err())
+	}
+}
+
+/// A canned response given by a `MockTransport`
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+	/// Status of the response
+	pub status: StatusCode,
+	/// Headers of the response
+	pub headers: HeaderMap,
+	/// Body of the response
+	pub body: String,
+	/// How long to wait before responding, for simulating a slow server
+	pub delay: Option<Duration>,
+}
+
+impl MockResponse {
+	/// Creates a response with a status code and body
+	/// # Arguments
+	/// * `status` - The HTTP status code of the response
+	/// * `body` - The body of the response
+	pub fn new(status: u16, body: &str) -> MockResponse {
+		MockResponse {
+			status: StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+			headers: HeaderMap::new(),
+			body: body.to_string(),
+			delay: None,
+		}
+	}
+
+	/// Creates a successful response with a json body
+	pub fn json(value: &Value) -> MockResponse {
+		MockResponse::new(200, &json::to_string(value).unwrap()).header("content-type", "application/json")
+	}
+
+	/// Adds a header to the response. Invalid headers are ignored.
+	pub fn header(mut self, name: &str, value: &str) -> MockResponse {
+		if let (Ok(name), Ok(value)) = (name.parse::<HeaderName>(), HeaderValue::from_str(value)) {
+			self.headers.append(name, value);
+		}
+		self
+	}
+
+	/// Makes the transport wait before responding
+	pub fn delay(mut self, delay: Duration) -> MockResponse {
+		self.delay = Some(delay);
+		self
+	}
+
+	fn to_response(&self) -> Response<Body> {
+		let mut response = Response::new(Body::from(self.body.clone()));
+		*response.status_mut() = self.status;
+		*response.headers_mut() = self.headers.clone();
+		response
+	}
+}
+
+/// A request that was sent through a `MockTransport`
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+	/// Method of the request
+	pub method: Method,
+	/// Uri of the request
+	pub uri: Uri,
+	/// Headers of the request
+	pub headers: HeaderMap,
+	/// Body of the request
+	pub body: String,
+}
+
+struct MockRoute {
+	method: Method,
+	path: String,
+	responses: VecDeque<MockResponse>,
+}
+
+#[derive(Default)]
+struct MockState {
+	routes: Vec<MockRoute>,
+	requests: Vec<MockRequest>,
+}
+
+/// A transport that answers requests with canned responses instead of sending them over the
+/// network. Responses are registered by method and path (the query string is ignored). If more
+/// than one response is registered for a route they are given out in order, and the last one is
+/// repeated once the others have been used up. Requests to routes without a response get a 404.
+///
+/// The transport can be cloned cheaply, and all clones share the same responses and requests. This
+/// allows keeping a handle to inspect the requests that were sent after giving a clone to a
+/// connection.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+	state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+	/// Creates a transport with no responses registered
+	pub fn new() -> MockTransport {
+		MockTransport::default()
+	}
+
+	/// Registers a response to a route
+	/// # Arguments
+	/// * `method` - The method of requests to answer
+	/// * `path` - The path of requests to answer, without the query string
+	/// * `response` - The response to answer with
+	pub fn on(&self, method: Method, path: &str, response: MockResponse) -> &MockTransport {
+		let mut state = self.state.lock().unwrap();
+		if let Some(route) = state.routes.iter_mut().find(|route| route.method == method && route.path == path) {
+			route.responses.push_back(response);
+			return self;
+		}
+		let mut responses = VecDeque::new();
+		responses.push_back(response);
+		state.routes.push(MockRoute { method, path: path.to_string(), responses });
+		self
+	}
+
+	/// Returns all requests that have been sent through this transport, in order
+	pub fn requests(&self) -> Vec<MockRequest> {
+		self.state.lock().unwrap().requests.clone()
+	}
+
+	fn respond(&self, req: MockRequest) -> MockResponse {
+		let mut state = self.state.lock().unwrap();
+		let response = match state.routes.iter_mut().find(|route| route.method == req.method && route.path == req.uri.path()) {
+			Some(ref mut route) if route.responses.len() > 1 => route.responses.pop_front().unwrap(),
+			Some(route) => route.responses[0].clone(),
+			None => {
+				warn!("No mock response for {} {}", req.method, req.uri);
+				MockResponse::new(404, "{\"message\": \"Not Found\", \"error\": 404}")
+			}
+		};
+		state.requests.push(req);
+		response
+	}
+}
+
+impl Transport for MockTransport {
+	fn send(&self, req: Request<Body>) -> TransportFuture {
+		let (parts, body) = req.into_parts();
+		let transport = self.clone();
+		Box::new(body.concat2().from_err().and_then(move |body| {
+			let req = MockRequest {
+				method: parts.method,
+				uri: parts.uri,
+				headers: parts.headers,
+				body: String::from_utf8_lossy(&body).into(),
+			};
+			trace!("Mock transport got request {:?}", req);
+			let response = transport.respond(req);
+			match response.delay {
+				Some(delay) => Box::new(Delay::new(Instant::now() + delay).from_err().map(move |_| response.to_response())) as TransportFuture,
+				None => Box::new(future::ok(response.to_response())),
+			}
+		}))
+	}
+}
@@ -0,0 +1,116 @@
+//! # User agents
+//! Reddit asks every client to identify itself with a user agent of the form
+//! `<platform>:<app ID>:<version string> (by /u/<reddit username>)`, and throttles clients with
+//! generic or spoofed ones. A `UserAgent` builds such a string, detecting the platform it runs on,
+//! and checks it before it is used, so a bad app name fails with an error when the connection is
+//! built instead of panicking.
+
+use std::env::consts::OS;
+use std::fmt;
+
+use hyper::header::HeaderValue;
+
+/// The user agent requests are sent with
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserAgent {
+	platform: String,
+	name: String,
+	version: String,
+	author: String,
+	components: Vec<String>,
+}
+
+impl UserAgent {
+	/// Creates a user agent for the platform orca is running on
+	/// # Arguments
+	/// * `name` - The name of the app, such as `com.example.mybot`
+	/// * `version` - The version of the app
+	/// * `author` - The reddit username of the author of the app, with or without `/u/`
+	pub fn new(name: &str, version: &str, author: &str) -> UserAgent {
+		let author = author.trim_start_matches('/');
+		let author = if author.starts_with("u/") { &author[2..] } else { author };
+
+		UserAgent {
+			platform: UserAgent::detect_platform().to_string(),
+			name: name.to_string(),
+			version: version.to_string(),
+			author: author.to_string(),
+			components: Vec::new(),
+		}
+	}
+
+	/// Returns the name of the platform orca is running on, such as `linux` or `windows`
+	pub fn detect_platform() -> &'static str {
+		if OS.is_empty() {
+			"unknown"
+		} else {
+			OS
+		}
+	}
+
+	/// Sets the platform instead of detecting it
+	pub fn platform(mut self, platform: &str) -> UserAgent {
+		self.platform = platform.to_string();
+		self
+	}
+
+	/// Adds a component to the end of the user agent, such as `orca/0.7`
+	pub fn component(mut self, component: &str) -> UserAgent {
+		self.components.push(component.to_string());
+		self
+	}
+
+	/// Checks that the user agent follows reddit's rules and can be sent as a header
+	pub fn validate(&self) -> Result<(), InvalidUserAgent> {
+		let invalid = |reason: &str| Err(InvalidUserAgent { useragent: self.to_string(), reason: reason.to_string() });
+
+		for &(part, value) in &[("platform", &self.platform), ("app name", &self.name), ("version", &self.version)] {
+			if value.trim().is_empty() {
+				return invalid(&format!("The {} is empty", part));
+			}
+			if value.contains(':') {
+				return invalid(&format!("The {} contains a colon", part));
+			}
+		}
+		if self.author.trim().is_empty() || self.author.contains(|c: char| c.is_whitespace() || c == '/' || c == ')') {
+			return invalid("The author is not a reddit username");
+		}
+		if self.components.iter().any(|component| component.trim().is_empty()) {
+			return invalid("A component is empty");
+		}
+		if self.to_string().chars().any(|c| c.is_control()) {
+			return invalid("It contains control characters such as newlines");
+		}
+		if HeaderValue::from_str(&self.to_string()).is_err() {
+			return invalid("It is not a valid header value");
+		}
+
+		Ok(())
+	}
+
+	/// Validates the user agent and converts it to a header value
+	pub fn to_header(&self) -> Result<HeaderValue, InvalidUserAgent> {
+		self.validate()?;
+		HeaderValue::from_str(&self.to_string()).map_err(|_| InvalidUserAgent { useragent: self.to_string(), reason: "It is not a valid header value".to_string() })
+	}
+}
+
+impl fmt::Display for UserAgent {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}:{}:{} (by /u/{})", self.platform, self.name, self.version, self.author)?;
+		for component in &self.components {
+			write!(f, " {}", component)?;
+		}
+		Ok(())
+	}
+}
+
+/// A user agent that doesn't follow reddit's rules or can't be sent as a header
+#[derive(Debug, Fail, Clone, PartialEq)]
+#[fail(display = "Invalid user agent {:?}: {}", useragent, reason)]
+pub struct InvalidUserAgent {
+	/// The user agent
+	pub useragent: String,
+	/// What is wrong with it
+	pub reason: String,
+}